@@ -0,0 +1,408 @@
+// src/ac.rs
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use crate::complex::Complex;
+use crate::error::CircuitError;
+use crate::stamp::Analysis;
+use crate::types::Circuit;
+
+// Result of an AC steady state analysis at a single frequency.
+// Voltages and currents follow the same conventions as the DC solver.
+pub struct AcSolution {
+    pub frequency: f64,
+    pub node_voltages: Vec<Complex>,
+    pub voltages: HashMap<String, Complex>,
+    pub currents: HashMap<String, Complex>,
+}
+impl AcSolution {
+    pub fn node_voltage(&self, node: usize) -> Option<Complex> {
+        self.node_voltages.get(node).copied()
+    }
+
+    pub fn voltage(&self, name: &str) -> Option<Complex> {
+        self.voltages.get(name).copied()
+    }
+
+    pub fn current(&self, name: &str) -> Option<Complex> {
+        self.currents.get(name).copied()
+    }
+}
+
+// AC steady state (phasor) analysis using Modified Nodal Analysis.
+// Only the ac_magnitude and ac_phase of independent sources drive the circuit;
+// their DC values are ignored.
+impl Circuit {
+    pub fn solve_ac(&self, frequency: f64) -> Result<AcSolution, CircuitError> {
+        if frequency < 0.0 || !frequency.is_finite() {
+            return Err(CircuitError::InvalidAnalysis("frequency must be a non-negative number".to_string()));
+        }
+
+        // nonlinear components are linearized about the DC operating point
+        let points = self.small_signal_points()?;
+        self.solve_ac_about(frequency, &points)
+    }
+
+    // solve at one frequency with the nonlinear components linearized about `points`
+    fn solve_ac_about(&self, frequency: f64, points: &HashMap<String, Vec<f64>>) -> Result<AcSolution, CircuitError> {
+        let omega = 2.0 * PI * frequency;
+        let layout = self.mna_layout(&Analysis::Ac { omega })?;
+        let system = self.assemble_ac(&layout, omega, points)?;
+        let solution = layout.solve(&system)?;
+
+        let node_voltages = layout.node_values(&solution);
+        let node_voltage = |node: Option<usize>| node.map(|n| node_voltages[n]).unwrap_or_default();
+
+        let mut voltages = HashMap::new();
+        let mut currents = HashMap::new();
+        for name in &layout.names {
+            let component = self.components[name].as_ref();
+            let base = component.component();
+            let (plus, minus) = component.voltage_terminals();
+            let voltage = node_voltage(base.node(plus)) - node_voltage(base.node(minus));
+            let point = points.get(name).map(|point| point.as_slice()).unwrap_or(&[]);
+            let current = component.ac_current(&layout.values(name, &solution, &[], point), omega);
+
+            voltages.insert(name.clone(), voltage);
+            if let Some(current) = current {
+                currents.insert(name.clone(), current);
+            }
+        }
+
+        Ok(AcSolution {
+            frequency,
+            node_voltages,
+            voltages,
+            currents,
+        })
+    }
+}
+
+// How the frequencies of an AC sweep are spaced
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SweepScale {
+    // `points` frequencies spread evenly from start to stop
+    Linear,
+    // `points` frequencies per decade
+    Decade,
+    // `points` frequencies per octave
+    Octave,
+}
+
+// A value recorded at every point of a sweep
+#[derive(Clone, Debug, PartialEq)]
+pub enum Probe {
+    // the voltage of a node
+    Node(usize),
+    // the voltage across a component, V(node1) - V(node2)
+    Voltage(String),
+    // the current through a component from node1 to node2
+    Current(String),
+}
+impl Probe {
+    fn label(&self) -> String {
+        match self {
+            Probe::Node(node) => format!("V({})", node),
+            Probe::Voltage(name) => format!("V({})", name),
+            Probe::Current(name) => format!("I({})", name),
+        }
+    }
+
+    fn read(&self, solution: &AcSolution) -> Option<Complex> {
+        match self {
+            Probe::Node(node) => solution.node_voltage(*node),
+            Probe::Voltage(name) => solution.voltage(name),
+            Probe::Current(name) => solution.current(name),
+        }
+    }
+}
+
+// Result of an AC sweep, values is indexed by probe and then by frequency
+pub struct AcSweep {
+    pub frequencies: Vec<f64>,
+    pub probes: Vec<Probe>,
+    pub values: Vec<Vec<Complex>>,
+}
+impl AcSweep {
+    pub fn values(&self, probe: &Probe) -> Option<&[Complex]> {
+        let index = self.probes.iter().position(|p| p == probe)?;
+        Some(&self.values[index])
+    }
+
+    // magnitude of a probe in decibels (20 * log10 |value|) at every frequency
+    pub fn magnitude_db(&self, probe: &Probe) -> Option<Vec<f64>> {
+        let values = self.values(probe)?;
+        Some(values.iter().map(|value| 20.0 * value.magnitude().log10()).collect())
+    }
+
+    // phase of a probe in degrees at every frequency
+    pub fn phase_degrees(&self, probe: &Probe) -> Option<Vec<f64>> {
+        let values = self.values(probe)?;
+        Some(values.iter().map(|value| value.phase_degrees()).collect())
+    }
+
+    // Bode plot data with one row per frequency and a magnitude (dB) and phase (degrees) column per probe
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frequency");
+        for probe in &self.probes {
+            let label = probe.label();
+            csv.push_str(&format!(",{} magnitude (dB),{} phase (deg)", label, label));
+        }
+        csv.push('\n');
+
+        for (i, frequency) in self.frequencies.iter().enumerate() {
+            csv.push_str(&frequency.to_string());
+            for values in &self.values {
+                csv.push_str(&format!(",{},{}", 20.0 * values[i].magnitude().log10(), values[i].phase_degrees()));
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+// AC sweep, solving the circuit at a range of frequencies and recording the probes at each one
+impl Circuit {
+    pub fn ac_sweep(&self, start: f64, stop: f64, points: usize, scale: SweepScale, probes: &[Probe]) -> Result<AcSweep, CircuitError> {
+        let frequencies = sweep_frequencies(start, stop, points, scale)?;
+
+        // check the probes before solving anything
+        for probe in probes {
+            match probe {
+                Probe::Node(node) if self.get_node(*node).is_none() => return Err(CircuitError::UnknownNode(*node)),
+                Probe::Voltage(name) | Probe::Current(name) if self.get_component(name).is_none() => {
+                    return Err(CircuitError::UnknownComponent(name.clone()))
+                }
+                _ => (),
+            }
+        }
+
+        // the operating point doesn't depend on the frequency, so it is only found once
+        let points = self.small_signal_points()?;
+        let mut values = vec![Vec::with_capacity(frequencies.len()); probes.len()];
+        for &frequency in &frequencies {
+            let solution = self.solve_ac_about(frequency, &points)?;
+            for (probe, values) in probes.iter().zip(values.iter_mut()) {
+                // a component without a branch current of its own has nothing to record
+                let value = probe
+                    .read(&solution)
+                    .ok_or_else(|| CircuitError::InvalidAnalysis(format!("{} has no value to record", probe.label())))?;
+                values.push(value);
+            }
+        }
+
+        Ok(AcSweep {
+            frequencies,
+            probes: probes.to_vec(),
+            values,
+        })
+    }
+}
+
+fn sweep_frequencies(start: f64, stop: f64, points: usize, scale: SweepScale) -> Result<Vec<f64>, CircuitError> {
+    if !start.is_finite() || !stop.is_finite() || start < 0.0 || stop < start {
+        return Err(CircuitError::InvalidAnalysis("sweep frequencies must satisfy 0 <= start <= stop".to_string()));
+    }
+    if points == 0 {
+        return Err(CircuitError::InvalidAnalysis("a sweep needs at least one point".to_string()));
+    }
+
+    let ratio = match scale {
+        SweepScale::Linear => {
+            if points == 1 {
+                return Ok(vec![start]);
+            }
+            let step = (stop - start) / (points - 1) as f64;
+            return Ok((0..points).map(|i| start + step * i as f64).collect());
+        }
+        SweepScale::Decade => 10.0_f64,
+        SweepScale::Octave => 2.0_f64,
+    };
+
+    if start <= 0.0 {
+        return Err(CircuitError::InvalidAnalysis("a logarithmic sweep must start above 0 Hz".to_string()));
+    }
+    // allow for rounding so that stop is included when it falls on a point
+    let count = (points as f64 * (stop / start).log(ratio) + 1e-9).floor() as usize + 1;
+    Ok((0..count).map(|i| start * ratio.powf(i as f64 / points as f64)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::any::Any;
+
+    use crate::stamp::Stamper;
+    use crate::types::{BaseComponent, Capacitor, Component, Diode, Inductor, Polarity, Resistor, VoltageSource};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9 * b.abs().max(1.0)
+    }
+
+    // V1 (1 V at `phase` degrees) driving R1 into C1 to ground, with a corner frequency of 1 kHz
+    fn low_pass(phase: f64) -> Circuit {
+        let mut source = VoltageSource::new("V1", 0.0, Polarity::Normal);
+        source.ac_magnitude = 1.0;
+        source.ac_phase = phase;
+        let mut circuit = Circuit::new();
+        circuit.add_component(source).unwrap();
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        circuit.add_component(Capacitor::new("C1", 1.0 / (2.0 * PI * 1e6))).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("R1", 1, "in"), ("R1", 2, "out"), ("C1", 1, "out"), ("C1", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        circuit
+    }
+
+    #[test]
+    fn low_pass_is_3db_down_and_lags_45_degrees_at_its_corner() {
+        let circuit = low_pass(0.0);
+        let solution = circuit.solve_ac(1e3).unwrap();
+        let out = solution.node_voltage(circuit.net_node("out").unwrap()).unwrap();
+        assert!(close(out.magnitude(), 1.0 / 2.0_f64.sqrt()));
+        assert!(close(out.phase_degrees(), -45.0));
+
+        // the capacitor current leads its voltage by 90 degrees
+        let current = solution.current("C1").unwrap();
+        let voltage = solution.voltage("C1").unwrap();
+        assert!(close(current.magnitude(), voltage.magnitude() * 2.0 * PI * 1e3 / (2.0 * PI * 1e6)));
+        assert!(close(current.phase_degrees() - voltage.phase_degrees(), 90.0));
+    }
+
+    #[test]
+    fn source_phase_shifts_every_phasor() {
+        let circuit = low_pass(30.0);
+        let solution = circuit.solve_ac(1e3).unwrap();
+        let out = solution.node_voltage(circuit.net_node("out").unwrap()).unwrap();
+        assert!(close(out.phase_degrees(), -15.0));
+
+        // at DC the capacitor is open and the output follows the source
+        let solution = circuit.solve_ac(0.0).unwrap();
+        let out = solution.node_voltage(circuit.net_node("out").unwrap()).unwrap();
+        assert!(close(out.magnitude(), 1.0));
+        assert!(close(out.phase_degrees(), 30.0));
+    }
+
+    #[test]
+    fn inductor_reactance_grows_with_frequency() {
+        let mut circuit = low_pass(0.0);
+        circuit.remove_component("C1").unwrap();
+        circuit.add_component(Inductor::new("L1", 1e3 / (2.0 * PI * 1e3))).unwrap();
+        circuit.attach("L1", 1, "out").unwrap();
+        circuit.attach("L1", 2, "0").unwrap();
+
+        let solution = circuit.solve_ac(1e3).unwrap();
+        let out = solution.node_voltage(circuit.net_node("out").unwrap()).unwrap();
+        assert!(close(out.magnitude(), 1.0 / 2.0_f64.sqrt()));
+        assert!(close(out.phase_degrees(), 45.0));
+
+        assert!(matches!(circuit.solve_ac(-1.0), Err(CircuitError::InvalidAnalysis(_))));
+    }
+
+    fn close_to(values: &[f64], expected: &[f64]) -> bool {
+        values.len() == expected.len() && values.iter().zip(expected).all(|(a, b)| close(*a, *b))
+    }
+
+    #[test]
+    fn spaces_sweep_frequencies_by_scale() {
+        let linear = sweep_frequencies(0.0, 1e3, 5, SweepScale::Linear).unwrap();
+        assert!(close_to(&linear, &[0.0, 250.0, 500.0, 750.0, 1e3]));
+        assert_eq!(sweep_frequencies(50.0, 1e3, 1, SweepScale::Linear).unwrap(), [50.0]);
+
+        // two points per decade and one per octave, both ending on stop
+        let decade = sweep_frequencies(10.0, 1e3, 2, SweepScale::Decade).unwrap();
+        assert!(close_to(&decade, &[10.0, 10.0 * 10f64.sqrt(), 100.0, 100.0 * 10f64.sqrt(), 1e3]));
+        let octave = sweep_frequencies(100.0, 900.0, 1, SweepScale::Octave).unwrap();
+        assert!(close_to(&octave, &[100.0, 200.0, 400.0, 800.0]));
+
+        for (start, stop, points, scale) in [
+            (0.0, 1e3, 10, SweepScale::Decade),
+            (1e3, 10.0, 10, SweepScale::Linear),
+            (10.0, 1e3, 0, SweepScale::Octave),
+            (10.0, f64::INFINITY, 10, SweepScale::Decade),
+        ] {
+            assert!(matches!(sweep_frequencies(start, stop, points, scale), Err(CircuitError::InvalidAnalysis(_))));
+        }
+    }
+
+    #[test]
+    fn writes_bode_plot_csv() {
+        let sweep = AcSweep {
+            frequencies: vec![1.0, 10.0],
+            probes: vec![Probe::Node(1), Probe::Current("R1".to_string())],
+            values: vec![
+                vec![Complex::new(1.0, 0.0), Complex::new(0.0, 10.0)],
+                vec![Complex::new(0.0, -0.1), Complex::new(-1.0, 0.0)],
+            ],
+        };
+        let csv = sweep.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "frequency,V(1) magnitude (dB),V(1) phase (deg),I(R1) magnitude (dB),I(R1) phase (deg)");
+        assert_eq!(lines[1], "1,0,0,-20,-90");
+        assert_eq!(lines[2], "10,20,90,0,180");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(sweep.magnitude_db(&Probe::Node(1)).unwrap(), [0.0, 20.0]);
+        assert_eq!(sweep.phase_degrees(&Probe::Node(2)), None);
+    }
+
+    #[test]
+    fn sweep_matches_a_solve_at_every_frequency() {
+        // the diode is linearized about the operating point found once for the whole sweep
+        let mut circuit = low_pass(0.0);
+        circuit.add_component(Diode::new("D1")).unwrap();
+        circuit.attach("D1", 1, "out").unwrap();
+        circuit.attach("D1", 2, "0").unwrap();
+        circuit.get_component_mut("V1").unwrap().as_any_mut().downcast_mut::<VoltageSource>().unwrap().voltage = 1.0;
+
+        let probes = [Probe::Voltage("C1".to_string()), Probe::Current("D1".to_string())];
+        let sweep = circuit.ac_sweep(10.0, 1e5, 3, SweepScale::Decade, &probes).unwrap();
+        assert_eq!(sweep.frequencies.len(), 13);
+        for (i, &frequency) in sweep.frequencies.iter().enumerate() {
+            let solution = circuit.solve_ac(frequency).unwrap();
+            assert_eq!(sweep.values[0][i], solution.voltage("C1").unwrap());
+            assert_eq!(sweep.values[1][i], solution.current("D1").unwrap());
+        }
+    }
+
+    // a conductance without a branch, which reports no current
+    struct Branchless {
+        component: BaseComponent,
+    }
+    impl Component for Branchless {
+        fn component(&self) -> &BaseComponent { &self.component }
+        fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+        fn as_any(&self) -> &dyn Any { self }
+        fn as_any_mut(&mut self) -> &mut dyn Any { self }
+        fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+            stamper.conductance(0, 1, 1.0);
+            Ok(())
+        }
+
+        fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+            stamper.conductance(0, 1, Complex::from(1.0));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rejects_probes_with_nothing_to_record() {
+        let mut circuit = low_pass(0.0);
+        let unknown = [Probe::Current("R9".to_string())];
+        let error = circuit.ac_sweep(10.0, 1e3, 2, SweepScale::Decade, &unknown).err().unwrap();
+        assert_eq!(error, CircuitError::UnknownComponent("R9".to_string()));
+
+        let base = BaseComponent {
+            nodes: Vec::new(),
+            name: "X1".to_string(),
+            current: None,
+            voltage: None,
+        };
+        circuit.add_component(Branchless { component: base }).unwrap();
+        circuit.attach("X1", 1, "out").unwrap();
+        circuit.attach("X1", 2, "0").unwrap();
+        let error = circuit.ac_sweep(10.0, 1e3, 2, SweepScale::Decade, &[Probe::Current("X1".to_string())]).err().unwrap();
+        assert_eq!(error, CircuitError::InvalidAnalysis("I(X1) has no value to record".to_string()));
+    }
+}
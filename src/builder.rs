@@ -0,0 +1,219 @@
+// src/builder.rs
+
+use crate::error::CircuitError;
+use crate::spice::parse_value;
+use crate::types::{Capacitor, Circuit, Component, CurrentSource, Inductor, Polarity, Resistor, VoltageSource};
+
+// Fluent circuit construction
+// Components are declared with the named nets their terminals sit on. The first error is
+// kept and returned by build, so calls can be chained without handling each one.
+//
+//     let circuit = CircuitBuilder::new()
+//         .voltage_source("V1", "vin", "gnd", 5.0)
+//         .resistor("R1", "vin", "gnd", 100.0)
+//         .build()?;
+pub struct CircuitBuilder {
+    circuit: Circuit,
+    error: Option<CircuitError>,
+}
+impl Default for CircuitBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CircuitBuilder {
+    pub fn new() -> Self {
+        Self {
+            circuit: Circuit::new(),
+            error: None,
+        }
+    }
+
+    // add a component with one net per terminal, in the order given by Component::terminals
+    pub fn component(mut self, component: impl Component + 'static, nets: &[&str]) -> Self {
+        if self.error.is_none() {
+            self.error = self.add(component, nets).err();
+        }
+        self
+    }
+
+    pub fn resistor(self, name: &str, node1: &str, node2: &str, resistance: f64) -> Self {
+        self.component(Resistor::new(name, resistance), &[node1, node2])
+    }
+
+    pub fn capacitor(self, name: &str, node1: &str, node2: &str, capacitance: f64) -> Self {
+        self.component(Capacitor::new(name, capacitance), &[node1, node2])
+    }
+
+    pub fn inductor(self, name: &str, node1: &str, node2: &str, inductance: f64) -> Self {
+        self.component(Inductor::new(name, inductance), &[node1, node2])
+    }
+
+    pub fn voltage_source(self, name: &str, plus: &str, minus: &str, voltage: f64) -> Self {
+        self.component(VoltageSource::new(name, voltage, Polarity::Normal), &[plus, minus])
+    }
+
+    // as in SPICE, the current flows from the first net through the source to the second
+    pub fn current_source(self, name: &str, from: &str, to: &str, current: f64) -> Self {
+        self.component(CurrentSource::new(name, current, Polarity::Inverted), &[from, to])
+    }
+
+    // add a two terminal element with its kind taken from the first letter of its name, as in
+    // a SPICE element line, and a value that may use engineering suffixes (4.7k, 10u, 5V)
+    pub fn element(self, name: &str, nets: [&str; 2], value: &str) -> Self {
+        let Some(number) = parse_value(value) else {
+            return self.fail(name, format!("{} is not a value", value));
+        };
+        let [node1, node2] = nets;
+        match name.chars().next().map(|letter| letter.to_ascii_uppercase()) {
+            Some('R') => self.resistor(name, node1, node2, number),
+            Some('C') => self.capacitor(name, node1, node2, number),
+            Some('L') => self.inductor(name, node1, node2, number),
+            Some('V') => self.voltage_source(name, node1, node2, number),
+            Some('I') => self.current_source(name, node1, node2, number),
+            _ => self.fail(name, "the name must start with R, C, L, V or I".to_string()),
+        }
+    }
+
+    // the circuit, once it passes Circuit::validate
+    pub fn build(self) -> Result<Circuit, CircuitError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let diagnostics = self.circuit.validate();
+        if !diagnostics.is_empty() {
+            return Err(CircuitError::InvalidTopology(diagnostics));
+        }
+        Ok(self.circuit)
+    }
+
+    fn add(&mut self, component: impl Component + 'static, nets: &[&str]) -> Result<(), CircuitError> {
+        let name = component.component().name.clone();
+        let terminals = component.terminals().len();
+        if nets.len() != terminals {
+            return Err(CircuitError::InvalidValue {
+                component: name,
+                reason: format!("{} nets given for {} terminals", nets.len(), terminals),
+            });
+        }
+
+        self.circuit.add_component(component)?;
+        for (terminal, net) in nets.iter().enumerate() {
+            self.circuit.attach(&name, terminal + 1, net)?;
+        }
+        Ok(())
+    }
+
+    fn fail(mut self, name: &str, reason: String) -> Self {
+        if self.error.is_none() {
+            self.error = Some(CircuitError::InvalidValue {
+                component: name.to_string(),
+                reason,
+            });
+        }
+        self
+    }
+}
+
+// Declare a circuit as a list of two terminal elements in the style of SPICE element lines,
+// each a name, two nets and a value, returning the validated circuit.
+//
+//     let circuit = circuit! {
+//         V1 vin gnd 5V;
+//         R1 vin gnd 100;
+//     }?;
+#[macro_export]
+macro_rules! circuit {
+    ($($name:ident $node1:tt $node2:tt $value:literal);* $(;)?) => {
+        $crate::builder::CircuitBuilder::new()
+            $(.element(stringify!($name), [stringify!($node1), stringify!($node2)], stringify!($value)))*
+            .build()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Diagnostic;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9 * b.abs().max(1.0)
+    }
+
+    fn get<'a, T: 'static>(circuit: &'a Circuit, name: &str) -> &'a T {
+        circuit.get_component(name).unwrap().as_any().downcast_ref::<T>().unwrap()
+    }
+
+    #[test]
+    fn builds_and_solves_a_chained_circuit() {
+        // I1 pushes 1 mA from ground up into out
+        let mut circuit = CircuitBuilder::new()
+            .voltage_source("V1", "in", "gnd", 5.0)
+            .resistor("R1", "in", "out", 1e3)
+            .resistor("R2", "out", "gnd", 1e3)
+            .current_source("I1", "gnd", "out", 1e-3)
+            .build()
+            .unwrap();
+        circuit.solve_dc().unwrap();
+        assert!(close(circuit.net_voltage("out").unwrap(), 3.0));
+        assert_eq!(circuit.net_name(get::<Resistor>(&circuit, "R2").component.nodes[1]).as_deref(), Some("gnd"));
+    }
+
+    #[test]
+    fn macro_values_take_engineering_suffixes() {
+        let circuit = crate::circuit! {
+            V1 vin gnd 5V;
+            R1 vin out 4.7k;
+            R2 out gnd 2meg;
+            C1 out gnd 10u;
+            L1 vin out 1m;
+        }
+        .unwrap();
+        assert_eq!(get::<VoltageSource>(&circuit, "V1").voltage, 5.0);
+        assert!(close(get::<Resistor>(&circuit, "R1").resistance, 4.7e3));
+        assert!(close(get::<Resistor>(&circuit, "R2").resistance, 2e6));
+        assert!(close(get::<Capacitor>(&circuit, "C1").capacitance, 10e-6));
+        assert!(close(get::<Inductor>(&circuit, "L1").inductance, 1e-3));
+        assert_eq!(circuit.net_node("vin"), Some(0));
+    }
+
+    #[test]
+    fn build_fails_when_validation_does() {
+        // out has nothing but R1 on it
+        let error = crate::circuit! {
+            V1 vin gnd 5;
+            R1 vin out 1k;
+        }
+        .err()
+        .unwrap();
+        let diagnostic = Diagnostic::UnconnectedTerminal {
+            component: "R1".to_string(),
+            terminal: "node2".to_string(),
+        };
+        assert_eq!(error, CircuitError::InvalidTopology(vec![diagnostic]));
+    }
+
+    #[test]
+    fn keeps_the_first_error() {
+        let error = CircuitBuilder::new()
+            .resistor("R1", "a", "gnd", 1.0)
+            .resistor("R1", "a", "gnd", 2.0)
+            .element("X1", ["a", "gnd"], "1")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(error, CircuitError::DuplicateComponent("R1".to_string()));
+
+        let error = CircuitBuilder::new().element("R1", ["a", "gnd"], "4.7q!").build().err().unwrap();
+        let reason = "4.7q! is not a value".to_string();
+        assert_eq!(error, CircuitError::InvalidValue { component: "R1".to_string(), reason });
+
+        let error = CircuitBuilder::new().element("X1", ["a", "gnd"], "1").build().err().unwrap();
+        let reason = "the name must start with R, C, L, V or I".to_string();
+        assert_eq!(error, CircuitError::InvalidValue { component: "X1".to_string(), reason });
+
+        let error = CircuitBuilder::new().component(Resistor::new("R1", 1.0), &["a"]).build().err().unwrap();
+        let reason = "1 nets given for 2 terminals".to_string();
+        assert_eq!(error, CircuitError::InvalidValue { component: "R1".to_string(), reason });
+    }
+}
@@ -0,0 +1,334 @@
+// src/dc.rs
+
+use std::collections::HashMap;
+
+use crate::error::CircuitError;
+use crate::stamp::Analysis;
+use crate::types::{Circuit, CurrentSource, VoltageSource};
+
+// node voltages (None for nodes with nothing connected), and the voltage and current of
+// every component, at an operating point
+struct OperatingPoint {
+    node_voltages: Vec<Option<f64>>,
+    components: Vec<(String, f64, Option<f64>)>,
+}
+
+// DC operating point analysis using Modified Nodal Analysis.
+//
+// Sign conventions for the results written back into each BaseComponent:
+//  - voltage is V(node1) - V(node2), or across Component::voltage_terminals (Vce for a BJT)
+//  - current is the current flowing through the component from node1 to node2
+impl Circuit {
+    pub fn solve_dc(&mut self) -> Result<(), CircuitError> {
+        let point = self.operating_point(&mut HashMap::new())?;
+
+        // write node voltages back
+        for (node, voltage) in self.nodes.iter_mut().zip(point.node_voltages) {
+            node.voltage = voltage;
+        }
+
+        // write component voltages and currents back
+        for (name, voltage, current) in point.components {
+            let base = self.components.get_mut(&name).unwrap().component_mut();
+            base.voltage = Some(voltage);
+            base.current = current;
+        }
+
+        Ok(())
+    }
+
+    // nonlinear components start from `points` and leave it at the points they converged at
+    fn operating_point(&self, points: &mut HashMap<String, Vec<f64>>) -> Result<OperatingPoint, CircuitError> {
+        let analysis = Analysis::Dc;
+        let layout = self.mna_layout(&analysis)?;
+        let solution = self.solve_real(&layout, &analysis, &HashMap::new(), points)?;
+
+        let node_voltages = layout.node_values(&solution);
+        let node_voltage = |node: Option<usize>| node.map(|n| node_voltages[n]).unwrap_or(0.0);
+
+        let mut components = Vec::with_capacity(layout.names.len());
+        for name in &layout.names {
+            let component = self.components[name].as_ref();
+            let base = component.component();
+            let (plus, minus) = component.voltage_terminals();
+            let voltage = node_voltage(base.node(plus)) - node_voltage(base.node(minus));
+            let point = points.get(name).map(|point| point.as_slice()).unwrap_or(&[]);
+            let current = component.current(&layout.values(name, &solution, &[], point), &analysis);
+            components.push((name.clone(), voltage, current));
+        }
+
+        // nodes with nothing connected have no voltage
+        let node_voltages = self
+            .nodes
+            .iter()
+            .map(|node| (!node.connected.is_empty()).then(|| node_voltages[node.id]))
+            .collect();
+        Ok(OperatingPoint { node_voltages, components })
+    }
+}
+
+// The values a DC sweep steps an independent source through
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepRange {
+    pub source: String,
+    pub start: f64,
+    pub stop: f64,
+    pub step: f64,
+}
+impl SweepRange {
+    pub fn new(source: &str, start: f64, stop: f64, step: f64) -> Self {
+        Self {
+            source: source.to_string(),
+            start,
+            stop,
+            step,
+        }
+    }
+
+    fn values(&self) -> Result<Vec<f64>, CircuitError> {
+        let span = (self.stop - self.start) / self.step;
+        if !span.is_finite() || span < 0.0 {
+            return Err(CircuitError::InvalidAnalysis(format!(
+                "the sweep of {} can't reach {} from {} in steps of {}",
+                self.source, self.stop, self.start, self.step
+            )));
+        }
+        // allow for rounding so that stop is included when it falls on a point
+        let count = (span + 1e-9).floor() as usize + 1;
+        Ok((0..count).map(|i| self.start + self.step * i as f64).collect())
+    }
+}
+
+// Result of a DC sweep with one entry per operating point.
+// source_values holds the value of every swept source at each point, and node_voltages,
+// voltages and currents are laid out like a TransientResult. As after solve_dc, nodes with
+// nothing connected have no voltage.
+pub struct DcSweep {
+    pub sources: Vec<String>,
+    pub source_values: Vec<Vec<f64>>,
+    pub node_voltages: Vec<Vec<Option<f64>>>,
+    pub voltages: HashMap<String, Vec<f64>>,
+    pub currents: HashMap<String, Vec<f64>>,
+}
+impl DcSweep {
+    // the values a swept source took at each point
+    pub fn source_values(&self, source: &str) -> Option<Vec<f64>> {
+        let index = self.sources.iter().position(|s| s == source)?;
+        Some(self.source_values.iter().map(|values| values[index]).collect())
+    }
+
+    pub fn node_voltage(&self, node: usize) -> Option<&[Option<f64>]> {
+        self.node_voltages.get(node).map(|values| values.as_slice())
+    }
+
+    pub fn voltage(&self, name: &str) -> Option<&[f64]> {
+        self.voltages.get(name).map(|values| values.as_slice())
+    }
+
+    pub fn current(&self, name: &str) -> Option<&[f64]> {
+        self.currents.get(name).map(|values| values.as_slice())
+    }
+}
+
+// DC sweep, re-solving the operating point while stepping the voltage of a VoltageSource
+// or the current of a CurrentSource. Nonlinear components start every point from the one
+// they converged at in the previous point. The swept sources are put back to their original
+// values afterwards and the results are not written back into the circuit.
+impl Circuit {
+    pub fn dc_sweep(&mut self, source: &str, start: f64, stop: f64, step: f64) -> Result<DcSweep, CircuitError> {
+        self.sweep(&[SweepRange::new(source, start, stop, step)])
+    }
+
+    // sweep `inner` across its whole range for every value of `outer`
+    pub fn dc_sweep_nested(&mut self, inner: SweepRange, outer: SweepRange) -> Result<DcSweep, CircuitError> {
+        if inner.source == outer.source {
+            return Err(CircuitError::InvalidAnalysis(format!("{} can't be swept twice", inner.source)));
+        }
+        self.sweep(&[inner, outer])
+    }
+
+    fn sweep(&mut self, ranges: &[SweepRange]) -> Result<DcSweep, CircuitError> {
+        // every combination of values, with the first range changing fastest
+        let mut points: Vec<Vec<f64>> = vec![Vec::new()];
+        for range in ranges {
+            let values = range.values()?;
+            points = values
+                .iter()
+                .flat_map(|&value| points.iter().map(move |point| {
+                    let mut point = point.clone();
+                    point.push(value);
+                    point
+                }))
+                .collect();
+        }
+
+        let original: Vec<f64> = ranges
+            .iter()
+            .map(|range| self.source_value(&range.source))
+            .collect::<Result<_, _>>()?;
+
+        let result = self.sweep_points(ranges, points);
+
+        for (range, value) in ranges.iter().zip(original) {
+            self.set_source_value(&range.source, value)?;
+        }
+
+        result
+    }
+
+    fn sweep_points(&mut self, ranges: &[SweepRange], points: Vec<Vec<f64>>) -> Result<DcSweep, CircuitError> {
+        let mut result = DcSweep {
+            sources: ranges.iter().map(|range| range.source.clone()).collect(),
+            source_values: Vec::with_capacity(points.len()),
+            node_voltages: vec![Vec::with_capacity(points.len()); self.nodes.len()],
+            voltages: HashMap::new(),
+            currents: HashMap::new(),
+        };
+
+        let mut operating_points = HashMap::new();
+        for values in points {
+            for (range, &value) in ranges.iter().zip(&values) {
+                self.set_source_value(&range.source, value)?;
+            }
+            let point = self.operating_point(&mut operating_points)?;
+
+            for (node, voltage) in point.node_voltages.into_iter().enumerate() {
+                result.node_voltages[node].push(voltage);
+            }
+            for (name, voltage, current) in point.components {
+                result.voltages.entry(name.clone()).or_default().push(voltage);
+                if let Some(current) = current {
+                    result.currents.entry(name).or_default().push(current);
+                }
+            }
+            result.source_values.push(values);
+        }
+
+        Ok(result)
+    }
+
+    fn source_value(&self, name: &str) -> Result<f64, CircuitError> {
+        let any = self
+            .get_component(name)
+            .ok_or_else(|| CircuitError::UnknownComponent(name.to_string()))?
+            .as_any();
+        if let Some(source) = any.downcast_ref::<VoltageSource>() {
+            Ok(source.voltage)
+        } else if let Some(source) = any.downcast_ref::<CurrentSource>() {
+            Ok(source.current)
+        } else {
+            Err(CircuitError::InvalidAnalysis(format!("{} is not an independent source", name)))
+        }
+    }
+
+    fn set_source_value(&mut self, name: &str, value: f64) -> Result<(), CircuitError> {
+        let any = self
+            .get_component_mut(name)
+            .ok_or_else(|| CircuitError::UnknownComponent(name.to_string()))?
+            .as_any_mut();
+        if let Some(source) = any.downcast_mut::<VoltageSource>() {
+            source.voltage = value;
+        } else if let Some(source) = any.downcast_mut::<CurrentSource>() {
+            source.current = value;
+        } else {
+            return Err(CircuitError::InvalidAnalysis(format!("{} is not an independent source", name)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Diode, Polarity, Resistor};
+
+    // V1 across R1 and R2 in series, with I1 pushing current into their junction "mid"
+    fn divider() -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 1.0, Polarity::Normal)).unwrap();
+        circuit.add_component(CurrentSource::new("I1", 0.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        circuit.add_component(Resistor::new("R2", 1e3)).unwrap();
+        for (component, terminal, net) in [
+            ("V1", 1, "in"),
+            ("V1", 2, "0"),
+            ("R1", 1, "in"),
+            ("R1", 2, "mid"),
+            ("R2", 1, "mid"),
+            ("R2", 2, "0"),
+            ("I1", 1, "mid"),
+            ("I1", 2, "0"),
+        ] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        circuit
+    }
+
+    #[test]
+    fn sweeps_a_source_and_puts_it_back() {
+        let mut circuit = divider();
+        // a component removed from the circuit leaves nodes with nothing connected
+        circuit.add_component(Resistor::new("R3", 1.0)).unwrap();
+        let orphan = circuit.get_component("R3").unwrap().component().nodes[0];
+        circuit.remove_component("R3").unwrap();
+
+        let sweep = circuit.dc_sweep("V1", 0.0, 4.0, 2.0).unwrap();
+        assert_eq!(sweep.source_values("V1").unwrap(), [0.0, 2.0, 4.0]);
+        let mid = circuit.net_node("mid").unwrap();
+        assert_eq!(sweep.node_voltage(mid).unwrap(), [Some(0.0), Some(1.0), Some(2.0)]);
+        assert_eq!(sweep.node_voltage(orphan).unwrap(), [None, None, None]);
+        assert_eq!(sweep.current("R2").unwrap(), [0.0, 1e-3, 2e-3]);
+
+        // solve_dc agrees on the orphan, and the source is back at 1 V
+        circuit.solve_dc().unwrap();
+        assert_eq!(circuit.nodes[orphan].voltage, None);
+        assert_eq!(circuit.net_voltage("mid"), Some(0.5));
+    }
+
+    #[test]
+    fn nested_sweeps_step_the_inner_source_fastest() {
+        let mut circuit = divider();
+        let sweep = circuit
+            .dc_sweep_nested(SweepRange::new("I1", 0.0, 1e-3, 1e-3), SweepRange::new("V1", 0.0, 2.0, 1.0))
+            .unwrap();
+        assert_eq!(sweep.sources, ["I1", "V1"]);
+        assert_eq!(sweep.source_values, [[0.0, 0.0], [1e-3, 0.0], [0.0, 1.0], [1e-3, 1.0], [0.0, 2.0], [1e-3, 2.0]]);
+        // V(mid) = V1 / 2 + I1 * 500
+        let mid = sweep.node_voltage(circuit.net_node("mid").unwrap()).unwrap();
+        for (values, voltage) in sweep.source_values.iter().zip(mid) {
+            assert!((voltage.unwrap() - (values[1] / 2.0 + values[0] * 500.0)).abs() < 1e-12);
+        }
+    }
+
+    fn clamped_divider() -> Circuit {
+        let mut circuit = divider();
+        circuit.add_component(Diode::new("D1")).unwrap();
+        circuit.attach("D1", 1, "mid").unwrap();
+        circuit.attach("D1", 2, "0").unwrap();
+        circuit
+    }
+
+    #[test]
+    fn nonlinear_sweeps_match_separate_solves() {
+        // every point starts from the previous one, which must not change where it converges
+        let sweep = clamped_divider().dc_sweep("V1", -5.0, 5.0, 0.5).unwrap();
+        for (i, values) in sweep.source_values.iter().enumerate() {
+            let mut single = clamped_divider();
+            single.get_component_mut("V1").unwrap().as_any_mut().downcast_mut::<VoltageSource>().unwrap().voltage = values[0];
+            single.solve_dc().unwrap();
+            let current = single.get_component("D1").unwrap().component().current.unwrap();
+            assert!((sweep.current("D1").unwrap()[i] - current).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_sweeps() {
+        let mut circuit = divider();
+        for (source, start, stop, step) in [("V1", 0.0, 1.0, -0.1), ("V1", 0.0, 1.0, 0.0), ("R1", 0.0, 1.0, 0.5)] {
+            assert!(matches!(circuit.dc_sweep(source, start, stop, step), Err(CircuitError::InvalidAnalysis(_))));
+        }
+        assert_eq!(circuit.dc_sweep("V9", 0.0, 1.0, 0.5).err(), Some(CircuitError::UnknownComponent("V9".to_string())));
+        let range = SweepRange::new("V1", 0.0, 1.0, 0.5);
+        assert!(circuit.dc_sweep_nested(range.clone(), range).is_err());
+    }
+}
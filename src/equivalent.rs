@@ -0,0 +1,204 @@
+// src/equivalent.rs
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use crate::complex::Complex;
+use crate::error::CircuitError;
+use crate::matrix::{LinearSystem, Scalar};
+use crate::mna::Layout;
+use crate::stamp::Analysis;
+use crate::types::Circuit;
+
+// Equivalent of a circuit seen from two nodes, node_a being the positive terminal.
+// Real for DC and complex (phasors and an impedance) for AC. The short circuit current flows
+// from node_a to node_b through the short, so it isn't finite when the impedance is zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Equivalent<T: Scalar> {
+    pub open_circuit_voltage: T,
+    pub short_circuit_current: T,
+    pub impedance: T,
+}
+impl<T: Scalar> Equivalent<T> {
+    pub fn admittance(&self) -> T {
+        T::from(1.0) / self.impedance
+    }
+}
+
+// Thevenin and Norton equivalents.
+// The open circuit voltage comes from solving the circuit as it is, and the impedance from
+// the voltage a 1 A test current between the nodes produces with every independent source
+// switched off, so dependent sources are accounted for. Nonlinear components are replaced by
+// their small signal model at the DC operating point when finding the impedance.
+impl Circuit {
+    pub fn thevenin(&self, node_a: usize, node_b: usize) -> Result<Equivalent<f64>, CircuitError> {
+        self.check_terminals(node_a, node_b)?;
+        let analysis = Analysis::Dc;
+        let layout = self.mna_layout(&analysis)?;
+        let mut points = HashMap::new();
+        let solution = self.solve_real(&layout, &analysis, &HashMap::new(), &mut points)?;
+        let system = self.assemble(&layout, &analysis, &HashMap::new(), &points)?;
+        equivalent(&layout, system, &solution, node_a, node_b)
+    }
+
+    // The Norton equivalent, a current source of short_circuit_current in parallel with
+    // admittance(). An Equivalent describes both forms, so this returns the same value as
+    // thevenin and only exists so callers can name the form they use.
+    pub fn norton(&self, node_a: usize, node_b: usize) -> Result<Equivalent<f64>, CircuitError> {
+        self.thevenin(node_a, node_b)
+    }
+
+    // the equivalent at a frequency, driven by the AC values of the sources
+    pub fn thevenin_ac(&self, node_a: usize, node_b: usize, frequency: f64) -> Result<Equivalent<Complex>, CircuitError> {
+        self.check_terminals(node_a, node_b)?;
+        if frequency < 0.0 || !frequency.is_finite() {
+            return Err(CircuitError::InvalidAnalysis("frequency must be a non-negative number".to_string()));
+        }
+        let omega = 2.0 * PI * frequency;
+        let points = self.small_signal_points()?;
+        let layout = self.mna_layout(&Analysis::Ac { omega })?;
+        let system = self.assemble_ac(&layout, omega, &points)?;
+        let solution = layout.solve(&system)?;
+        equivalent(&layout, system, &solution, node_a, node_b)
+    }
+
+    // the Norton form of thevenin_ac, which is the same value
+    pub fn norton_ac(&self, node_a: usize, node_b: usize, frequency: f64) -> Result<Equivalent<Complex>, CircuitError> {
+        self.thevenin_ac(node_a, node_b, frequency)
+    }
+
+    fn check_terminals(&self, node_a: usize, node_b: usize) -> Result<(), CircuitError> {
+        let net_map = self.net_map();
+        let connected = self.connected_nets(&net_map);
+        for node in [node_a, node_b] {
+            if self.get_node(node).is_none() {
+                return Err(CircuitError::UnknownNode(node));
+            }
+            // the solvers leave out nets with nothing connected, such as those a removed component leaves
+            if !connected[net_map[node]] {
+                return Err(CircuitError::InvalidAnalysis(format!("node {} has nothing connected to it", node)));
+            }
+        }
+        if self.net_of(node_a) == self.net_of(node_b) {
+            return Err(CircuitError::InvalidAnalysis(format!("nodes {} and {} are on the same net", node_a, node_b)));
+        }
+        Ok(())
+    }
+}
+
+fn equivalent<T: Scalar>(layout: &Layout, mut system: LinearSystem<T>, solution: &[T], node_a: usize, node_b: usize) -> Result<Equivalent<T>, CircuitError> {
+    let (a, b) = (layout.node_index[node_a], layout.node_index[node_b]);
+    let voltage = |values: &[T]| {
+        let value = |index: Option<usize>| index.map(|i| values[i]).unwrap_or(T::from(0.0));
+        value(a) - value(b)
+    };
+    let open_circuit_voltage = voltage(solution);
+
+    // switch every source off and drive 1 A into node_a and out of node_b
+    system.b = vec![T::from(0.0); system.size];
+    system.stamp_current(b, a, T::from(1.0));
+    let mut impedance = voltage(&layout.solve(&system)?);
+    if impedance.magnitude() == 0.0 {
+        // an ideal source can leave a negative zero
+        impedance = T::from(0.0);
+    }
+
+    Ok(Equivalent {
+        open_circuit_voltage,
+        short_circuit_current: open_circuit_voltage / impedance,
+        impedance,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Capacitor, Component, Polarity, Resistor, VoltageSource};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9 * b.abs().max(1.0)
+    }
+
+    // V1 (10 V DC, 1 V AC) driving R1 and then R2 or C1 to ground, seen from "out"
+    fn divider(lower: impl Component + 'static) -> Circuit {
+        let mut source = VoltageSource::new("V1", 10.0, Polarity::Normal);
+        source.ac_magnitude = 1.0;
+        let name = lower.component().name.clone();
+        let mut circuit = Circuit::new();
+        circuit.add_component(source).unwrap();
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        circuit.add_component(lower).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("R1", 1, "in"), ("R1", 2, "out"), (&name, 1, "out"), (&name, 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        circuit
+    }
+
+    fn terminals(circuit: &Circuit) -> (usize, usize) {
+        (circuit.net_node("out").unwrap(), circuit.net_node("0").unwrap())
+    }
+
+    #[test]
+    fn finds_the_dc_equivalent_of_a_divider() {
+        let circuit = divider(Resistor::new("R2", 1e3));
+        let (out, ground) = terminals(&circuit);
+        let equivalent = circuit.thevenin(out, ground).unwrap();
+        assert!(close(equivalent.open_circuit_voltage, 5.0));
+        assert!(close(equivalent.impedance, 500.0));
+        // shorting out to ground leaves only R1 carrying the current
+        assert!(close(equivalent.short_circuit_current, 10e-3));
+        assert!(close(equivalent.admittance(), 2e-3));
+        assert_eq!(circuit.norton(out, ground).unwrap(), equivalent);
+
+        // seen the other way round the voltage and current change sign
+        let reversed = circuit.thevenin(ground, out).unwrap();
+        assert!(close(reversed.open_circuit_voltage, -5.0));
+        assert!(close(reversed.short_circuit_current, -10e-3));
+        assert!(close(reversed.impedance, 500.0));
+    }
+
+    #[test]
+    fn ideal_sources_have_no_finite_short_circuit_current() {
+        let circuit = divider(Resistor::new("R2", 1e3));
+        let (input, ground) = (circuit.net_node("in").unwrap(), circuit.net_node("0").unwrap());
+        let equivalent = circuit.thevenin(input, ground).unwrap();
+        assert_eq!(equivalent.impedance, 0.0);
+        assert!(equivalent.short_circuit_current.is_infinite());
+    }
+
+    #[test]
+    fn finds_the_ac_equivalent_of_a_low_pass() {
+        // 1 / (2 pi f C) is 1k at 1 kHz
+        let circuit = divider(Capacitor::new("C1", 1.0 / (2.0 * PI * 1e6)));
+        let (out, ground) = terminals(&circuit);
+        let equivalent = circuit.thevenin_ac(out, ground, 1e3).unwrap();
+        // 1k in parallel with -1k j
+        assert!(close(equivalent.impedance.re, 500.0));
+        assert!(close(equivalent.impedance.im, -500.0));
+        assert!(close(equivalent.open_circuit_voltage.magnitude(), 0.5_f64.sqrt()));
+        assert!(close(equivalent.open_circuit_voltage.phase_degrees(), -45.0));
+        // the short bypasses C1, so the current is set by R1 alone and in phase with V1
+        assert!(close(equivalent.short_circuit_current.re, 1e-3));
+        assert!(close(equivalent.short_circuit_current.im, 0.0));
+        assert_eq!(circuit.norton_ac(out, ground, 1e3).unwrap(), equivalent);
+
+        assert!(matches!(circuit.thevenin_ac(out, ground, -1.0), Err(CircuitError::InvalidAnalysis(_))));
+    }
+
+    #[test]
+    fn rejects_terminals_it_cannot_look_into() {
+        let mut circuit = divider(Resistor::new("R2", 1e3));
+        let (out, ground) = terminals(&circuit);
+        assert_eq!(circuit.thevenin(out, 99).unwrap_err(), CircuitError::UnknownNode(99));
+        // R1 and R2 both have a node on "out"
+        let same_net = circuit.get_component("R2").unwrap().component().nodes[0];
+        assert!(matches!(circuit.thevenin(out, same_net), Err(CircuitError::InvalidAnalysis(_))));
+
+        circuit.add_component(Resistor::new("R3", 1.0)).unwrap();
+        let orphan = circuit.get_component("R3").unwrap().component().nodes[0];
+        circuit.remove_component("R3").unwrap();
+        let error = circuit.thevenin(orphan, ground).unwrap_err();
+        assert_eq!(error, CircuitError::InvalidAnalysis(format!("node {} has nothing connected to it", orphan)));
+        assert!(circuit.thevenin_ac(ground, orphan, 1e3).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+// src/error.rs
+
+use std::error::Error;
+use std::fmt;
+
+use crate::validation::Diagnostic;
+
+// Errors produced while building or solving a circuit
+#[derive(Clone, Debug, PartialEq)]
+pub enum CircuitError {
+    UnknownNode(usize),
+    SelfConnection(usize),
+    DuplicateComponent(String),
+    UnknownComponent(String),
+    UnknownWire(usize),
+    UnknownTerminal { component: String, terminal: String },
+    AlreadyAttached(usize, String),
+    InvalidValue { component: String, reason: String },
+    UnsupportedComponent { component: String, reason: String },
+    InvalidAnalysis(String),
+    MissingGround(usize),
+    MultipleGrounds(usize, usize),
+    FloatingNode(usize),
+    SingularMatrix,
+    NoConvergence(usize),
+    InvalidTopology(Vec<Diagnostic>),
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CircuitError::UnknownNode(node) => write!(f, "Node {} does not exist", node),
+            CircuitError::SelfConnection(node) => write!(f, "Cannot connect node {} to itself", node),
+            CircuitError::DuplicateComponent(name) => write!(f, "A component named {} already exists", name),
+            CircuitError::UnknownComponent(name) => write!(f, "No component named {}", name),
+            CircuitError::UnknownWire(id) => write!(f, "Wire {} does not exist", id),
+            CircuitError::UnknownTerminal { component, terminal } => write!(f, "{} has no terminal {}", component, terminal),
+            CircuitError::AlreadyAttached(node, net) => write!(f, "Node {} is already on net {}", node, net),
+            CircuitError::InvalidValue { component, reason } => write!(f, "Invalid value for {}: {}", component, reason),
+            CircuitError::UnsupportedComponent { component, reason } => write!(f, "{} is not supported: {}", component, reason),
+            CircuitError::InvalidAnalysis(reason) => write!(f, "Invalid analysis: {}", reason),
+            CircuitError::MissingGround(node) => write!(f, "The part of the circuit containing node {} has no ground", node),
+            CircuitError::MultipleGrounds(a, b) => write!(f, "Ground nodes {} and {} are in the same part of the circuit but not connected", a, b),
+            CircuitError::FloatingNode(node) => write!(f, "Node {} has no path to the reference node", node),
+            CircuitError::SingularMatrix => write!(f, "Circuit matrix is singular"),
+            CircuitError::NoConvergence(iterations) => write!(f, "Newton-Raphson iteration did not converge after {} iterations", iterations),
+            CircuitError::InvalidTopology(diagnostics) => {
+                let diagnostics: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+                write!(f, "Circuit failed validation: {}", diagnostics.join("; "))
+            }
+        }
+    }
+}
+
+impl Error for CircuitError {}
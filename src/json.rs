@@ -0,0 +1,1522 @@
+// src/json.rs
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::ac::{AcSolution, AcSweep, Probe};
+use crate::complex::Complex;
+use crate::dc::DcSweep;
+use crate::error::CircuitError;
+use crate::transient::TransientResult;
+use crate::types::{
+    Bjt, BjtType, Capacitor, Ccvs, Cccs, Circuit, Component, CurrentSource, Diode, Inductor, Mosfet, MosfetType, Node,
+    OpAmp, Polarity, Resistor, Vccs, Vcvs, VoltageSource, Wire,
+};
+use crate::waveform::Waveform;
+
+// JSON documents
+// Circuits and solve results are saved as a JSON object holding the schema version, the kind
+// of document and its contents:
+//
+//     {"version": 1, "kind": "circuit", "nodes": [...], "wires": [...], "components": [...]}
+//
+// The kinds are "circuit", "ac_solution", "ac_sweep", "dc_sweep" and "transient". Complex
+// numbers are written as {"re": .., "im": ..}, numbers JSON can't hold as "inf", "-inf"
+// and "nan", and missing values, such as the voltage of a node with nothing connected, as
+// null. A solved component keeps its voltage and current under "result", apart from its
+// parameters. Objects may not repeat a key. Documents from any other schema version are
+// rejected so they can be migrated.
+pub const SCHEMA_VERSION: u64 = 1;
+
+// A parsed JSON value. Objects keep their keys in the order they were written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonError {
+    // the text is not valid JSON
+    Syntax { line: usize, column: usize, message: String },
+    // valid JSON that doesn't follow the schema
+    Schema(String),
+    // a document written with another version of the schema
+    UnsupportedVersion(u64),
+    // a component with no JSON representation, such as one defined outside this crate
+    UnsupportedComponent(String),
+    // the circuit described can't be built
+    Circuit(CircuitError),
+}
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Syntax { line, column, message } => write!(f, "line {}, column {}: {}", line, column, message),
+            JsonError::Schema(message) => write!(f, "{}", message),
+            JsonError::UnsupportedVersion(version) => write!(f, "Schema version {} is not supported, expected {}", version, SCHEMA_VERSION),
+            JsonError::UnsupportedComponent(name) => write!(f, "{} can't be saved as JSON", name),
+            JsonError::Circuit(error) => write!(f, "{}", error),
+        }
+    }
+}
+impl Error for JsonError {}
+impl From<CircuitError> for JsonError {
+    fn from(error: CircuitError) -> Self {
+        JsonError::Circuit(error)
+    }
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("unexpected text after the value"));
+        }
+        Ok(value)
+    }
+
+    // Write the value with two space indentation. Arrays that only hold numbers, strings and
+    // other plain values are kept on a single line.
+    pub fn to_string_pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        out.push('\n');
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Json::Number(value) => out.push_str(&number_text(*value)),
+            Json::String(value) => write_string(out, value),
+            Json::Array(items) if items.iter().all(|item| !matches!(item, Json::Array(_) | Json::Object(_))) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    item.write(out, indent);
+                }
+                out.push(']');
+            }
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(&"  ".repeat(indent + 1));
+                    item.write(out, indent + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push(']');
+            }
+            Json::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Object(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    out.push_str(&"  ".repeat(indent + 1));
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push('}');
+            }
+        }
+    }
+}
+
+// numbers use the shortest text that reads back as the same value
+fn number_text(value: f64) -> String {
+    if value.is_nan() {
+        "\"nan\"".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "\"inf\"" } else { "\"-inf\"" }.to_string()
+    } else if value != 0.0 && (value.abs() < 1e-3 || value.abs() >= 1e9) {
+        format!("{:e}", value)
+    } else {
+        format!("{}", value)
+    }
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// how deeply arrays and objects may nest, which bounds the recursion of the parser
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize, // arrays and objects open at the current position
+}
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> JsonError {
+        let before = &self.chars[..self.position.min(self.chars.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        JsonError::Syntax {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        if self.peek() != Some(expected) {
+            return Err(self.error(format!("expected '{}'", expected)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('n') => self.literal("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of text")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Json, JsonError>) -> Result<Json, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!("arrays and objects nest more than {} deep", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn literal(&mut self, text: &str, value: Json) -> Result<Json, JsonError> {
+        let end = self.position + text.chars().count();
+        if end > self.chars.len() || self.chars[self.position..end].iter().copied().ne(text.chars()) {
+            return Err(self.error("expected a value"));
+        }
+        self.position = end;
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        match text.parse::<f64>() {
+            Ok(value) if is_number(&text) => Ok(Json::Number(value)),
+            _ => {
+                self.position = start;
+                Err(self.error(format!("{} is not a number", text)))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.position += 1;
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escape = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                    self.position += 1;
+                    match escape {
+                        '"' | '\\' | '/' => value.push(escape),
+                        'b' => value.push('\u{8}'),
+                        'f' => value.push('\u{c}'),
+                        'n' => value.push('\n'),
+                        'r' => value.push('\r'),
+                        't' => value.push('\t'),
+                        'u' => {
+                            let mut code = self.hex()?;
+                            // characters outside the basic plane are written as a surrogate pair
+                            if (0xd800..0xdc00).contains(&code) {
+                                let start = self.position;
+                                let low = if self.chars[self.position..].starts_with(&['\\', 'u']) {
+                                    self.position += 2;
+                                    self.hex()?
+                                } else {
+                                    0
+                                };
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    self.position = start;
+                                    return Err(self.error("unpaired surrogate in unicode escape"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            value.push(char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))?);
+                        }
+                        _ => {
+                            self.position -= 1;
+                            return Err(self.error(format!("invalid escape \\{}", escape)));
+                        }
+                    }
+                }
+                c if (c as u32) < 0x20 => {
+                    self.position -= 1;
+                    return Err(self.error("control character in string"));
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let end = self.position + 4;
+        let text: String = self.chars.get(self.position..end).unwrap_or_default().iter().collect();
+        let code = u32::from_str_radix(&text, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.position = end;
+        Ok(code)
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+// The JSON number grammar, which is stricter than Rust's: an optional minus, an integer part
+// without leading zeros, and a fraction and exponent that have at least one digit each
+fn is_number(text: &str) -> bool {
+    let digits = |text: &str| text.bytes().take_while(u8::is_ascii_digit).count();
+    let rest = text.strip_prefix('-').unwrap_or(text);
+    let integer = digits(rest);
+    if integer == 0 || (integer > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &rest[integer..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let count = digits(fraction);
+        if count == 0 {
+            return false;
+        }
+        rest = &fraction[count..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let count = digits(exponent);
+        if count == 0 {
+            return false;
+        }
+        rest = &exponent[count..];
+    }
+    rest.is_empty()
+}
+
+// Building documents
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+fn document(kind: &str, mut fields: Vec<(&str, Json)>) -> String {
+    fields.insert(0, ("version", Json::Number(SCHEMA_VERSION as f64)));
+    fields.insert(1, ("kind", string(kind)));
+    object(fields).to_string_pretty()
+}
+
+fn string(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+fn number(value: f64) -> Json {
+    Json::Number(value)
+}
+
+fn optional(value: Option<f64>) -> Json {
+    value.map(Json::Number).unwrap_or(Json::Null)
+}
+
+fn numbers(values: &[f64]) -> Json {
+    Json::Array(values.iter().map(|value| number(*value)).collect())
+}
+
+fn table(rows: &[Vec<f64>]) -> Json {
+    Json::Array(rows.iter().map(|row| numbers(row)).collect())
+}
+
+fn optional_table(rows: &[Vec<Option<f64>>]) -> Json {
+    Json::Array(rows.iter().map(|row| Json::Array(row.iter().map(|value| optional(*value)).collect())).collect())
+}
+
+fn complex(value: Complex) -> Json {
+    object(vec![("re", number(value.re)), ("im", number(value.im))])
+}
+
+// an object with its keys sorted, so documents don't depend on HashMap order
+fn map<T>(values: &HashMap<String, T>, json: impl Fn(&T) -> Json) -> Json {
+    let mut names: Vec<&String> = values.keys().collect();
+    names.sort();
+    Json::Object(names.into_iter().map(|name| (name.clone(), json(&values[name]))).collect())
+}
+
+// Reading documents
+
+// the fields of an object, with what it is for error messages
+struct Fields<'a> {
+    context: String,
+    fields: &'a [(String, Json)],
+}
+impl<'a> Fields<'a> {
+    fn new(value: &'a Json, context: impl Into<String>) -> Result<Self, JsonError> {
+        let context = context.into();
+        let Json::Object(fields) = value else {
+            return Err(JsonError::Schema(format!("{} must be an object", context)));
+        };
+        for (i, (key, _)) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|(other, _)| other == key) {
+                return Err(JsonError::Schema(format!("{}.{} is given more than once", context, key)));
+            }
+        }
+        Ok(Self { context, fields })
+    }
+
+    fn context(&self, key: &str) -> String {
+        format!("{}.{}", self.context, key)
+    }
+
+    // a field that is missing or null is None
+    fn optional(&self, key: &str) -> Option<&'a Json> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+            .filter(|value| **value != Json::Null)
+    }
+
+    fn get(&self, key: &str) -> Result<&'a Json, JsonError> {
+        self.optional(key)
+            .ok_or_else(|| JsonError::Schema(format!("{} is missing", self.context(key))))
+    }
+
+    fn number(&self, key: &str) -> Result<f64, JsonError> {
+        read_number(self.get(key)?, &self.context(key))
+    }
+
+    fn optional_number(&self, key: &str) -> Result<Option<f64>, JsonError> {
+        self.optional(key).map(|value| read_number(value, &self.context(key))).transpose()
+    }
+
+    // overwrite a parameter when the field is given
+    fn set(&self, key: &str, parameter: &mut f64) -> Result<(), JsonError> {
+        if let Some(value) = self.optional_number(key)? {
+            *parameter = value;
+        }
+        Ok(())
+    }
+
+    fn index(&self, key: &str) -> Result<usize, JsonError> {
+        read_index(self.get(key)?, &self.context(key))
+    }
+
+    fn string(&self, key: &str) -> Result<&'a str, JsonError> {
+        read_string(self.get(key)?, &self.context(key))
+    }
+
+    fn array(&self, key: &str) -> Result<&'a [Json], JsonError> {
+        read_array(self.get(key)?, &self.context(key))
+    }
+
+    fn numbers(&self, key: &str) -> Result<Vec<f64>, JsonError> {
+        read_numbers(self.get(key)?, &self.context(key))
+    }
+
+    fn table(&self, key: &str) -> Result<Vec<Vec<f64>>, JsonError> {
+        let context = self.context(key);
+        self.array(key)?
+            .iter()
+            .enumerate()
+            .map(|(i, row)| read_numbers(row, &format!("{}[{}]", context, i)))
+            .collect()
+    }
+
+    fn optional_table(&self, key: &str) -> Result<Vec<Vec<Option<f64>>>, JsonError> {
+        let context = self.context(key);
+        self.array(key)?
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let context = format!("{}[{}]", context, i);
+                read_array(row, &context)?
+                    .iter()
+                    .enumerate()
+                    .map(|(j, value)| match value {
+                        Json::Null => Ok(None),
+                        value => read_number(value, &format!("{}[{}]", context, j)).map(Some),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn map<T>(&self, key: &str, read: impl Fn(&Json, &str) -> Result<T, JsonError>) -> Result<HashMap<String, T>, JsonError> {
+        let fields = Fields::new(self.get(key)?, self.context(key))?;
+        fields
+            .fields
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), read(value, &fields.context(name))?)))
+            .collect()
+    }
+}
+
+fn read_number(value: &Json, context: &str) -> Result<f64, JsonError> {
+    match value {
+        Json::Number(value) => Ok(*value),
+        Json::String(text) if text == "inf" => Ok(f64::INFINITY),
+        Json::String(text) if text == "-inf" => Ok(f64::NEG_INFINITY),
+        Json::String(text) if text == "nan" => Ok(f64::NAN),
+        _ => Err(JsonError::Schema(format!("{} must be a number", context))),
+    }
+}
+
+fn read_index(value: &Json, context: &str) -> Result<usize, JsonError> {
+    match value {
+        Json::Number(value) if *value >= 0.0 && value.fract() == 0.0 => Ok(*value as usize),
+        _ => Err(JsonError::Schema(format!("{} must be a whole number", context))),
+    }
+}
+
+fn read_string<'a>(value: &'a Json, context: &str) -> Result<&'a str, JsonError> {
+    match value {
+        Json::String(text) => Ok(text),
+        _ => Err(JsonError::Schema(format!("{} must be a string", context))),
+    }
+}
+
+fn read_array<'a>(value: &'a Json, context: &str) -> Result<&'a [Json], JsonError> {
+    match value {
+        Json::Array(items) => Ok(items),
+        _ => Err(JsonError::Schema(format!("{} must be an array", context))),
+    }
+}
+
+fn read_numbers(value: &Json, context: &str) -> Result<Vec<f64>, JsonError> {
+    read_array(value, context)?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| read_number(item, &format!("{}[{}]", context, i)))
+        .collect()
+}
+
+fn read_complex(value: &Json, context: &str) -> Result<Complex, JsonError> {
+    let fields = Fields::new(value, context)?;
+    Ok(Complex::new(fields.number("re")?, fields.number("im")?))
+}
+
+fn read_complexes(value: &Json, context: &str) -> Result<Vec<Complex>, JsonError> {
+    read_array(value, context)?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| read_complex(item, &format!("{}[{}]", context, i)))
+        .collect()
+}
+
+// parse a document and check its version and kind
+fn read_document<'a>(json: &'a Json, kind: &str) -> Result<Fields<'a>, JsonError> {
+    let fields = Fields::new(json, "document")?;
+    let version = fields.index("version")? as u64;
+    if version != SCHEMA_VERSION {
+        return Err(JsonError::UnsupportedVersion(version));
+    }
+    let found = fields.string("kind")?;
+    if found != kind {
+        return Err(JsonError::Schema(format!("expected a {} document, found {}", kind, found)));
+    }
+    Ok(fields)
+}
+
+// Circuits
+impl Circuit {
+    pub fn to_json(&self) -> Result<String, JsonError> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                object(vec![
+                    ("id", number(node.id as f64)),
+                    ("label", node.label.as_deref().map(string).unwrap_or(Json::Null)),
+                    ("ground", Json::Bool(node.ground)),
+                    ("voltage", optional(node.voltage)),
+                ])
+            })
+            .collect();
+
+        let mut wires: Vec<&Wire> = self.wires.values().collect();
+        wires.sort_by_key(|wire| wire.id);
+        let wires = wires
+            .into_iter()
+            .map(|wire| {
+                object(vec![
+                    ("id", number(wire.id as f64)),
+                    ("node1", number(wire.node1 as f64)),
+                    ("node2", number(wire.node2 as f64)),
+                ])
+            })
+            .collect();
+
+        let mut names: Vec<&String> = self.components.keys().collect();
+        names.sort();
+        let components = names
+            .into_iter()
+            .map(|name| component_json(self.components[name].as_ref()))
+            .collect::<Result<Vec<Json>, JsonError>>()?;
+
+        Ok(document(
+            "circuit",
+            vec![("nodes", Json::Array(nodes)), ("wires", Json::Array(wires)), ("components", Json::Array(components))],
+        ))
+    }
+
+    pub fn from_json(text: &str) -> Result<Circuit, JsonError> {
+        let json = Json::parse(text)?;
+        let document = read_document(&json, "circuit")?;
+        let mut circuit = Circuit::new();
+
+        for (i, value) in document.array("nodes")?.iter().enumerate() {
+            let fields = Fields::new(value, format!("nodes[{}]", i))?;
+            if fields.index("id")? != i {
+                return Err(JsonError::Schema(format!("{} must be {}", fields.context("id"), i)));
+            }
+            let mut node = Node::new(i);
+            node.label = fields.optional("label").map(|_| fields.string("label")).transpose()?.map(str::to_string);
+            node.ground = matches!(fields.optional("ground"), Some(Json::Bool(true)));
+            node.voltage = fields.optional_number("voltage")?;
+            circuit.nodes.push(node);
+        }
+
+        for (i, value) in document.array("components")?.iter().enumerate() {
+            let component = read_component(&Fields::new(value, format!("components[{}]", i))?)?;
+            circuit.insert_component(component)?;
+        }
+
+        for (i, value) in document.array("wires")?.iter().enumerate() {
+            let fields = Fields::new(value, format!("wires[{}]", i))?;
+            let id = fields.index("id")?;
+            if circuit.wires.contains_key(&id) {
+                return Err(JsonError::Schema(format!("wire {} is listed twice", id)));
+            }
+            circuit.insert_wire(Wire::new(id, fields.index("node1")?, fields.index("node2")?))?;
+        }
+
+        Ok(circuit)
+    }
+}
+
+fn component_json(component: &dyn Component) -> Result<Json, JsonError> {
+    let base = component.component();
+    let any = component.as_any();
+
+    let (kind, parameters) = if let Some(resistor) = any.downcast_ref::<Resistor>() {
+        ("resistor", vec![("resistance", number(resistor.resistance))])
+    } else if let Some(capacitor) = any.downcast_ref::<Capacitor>() {
+        (
+            "capacitor",
+            vec![("capacitance", number(capacitor.capacitance)), ("initial_voltage", optional(capacitor.initial_voltage))],
+        )
+    } else if let Some(inductor) = any.downcast_ref::<Inductor>() {
+        (
+            "inductor",
+            vec![("inductance", number(inductor.inductance)), ("initial_current", optional(inductor.initial_current))],
+        )
+    } else if let Some(source) = any.downcast_ref::<VoltageSource>() {
+        (
+            "voltage_source",
+            vec![
+                ("voltage", number(source.voltage)),
+                ("polarity", polarity_json(&source.polarity)),
+                ("ac_magnitude", number(source.ac_magnitude)),
+                ("ac_phase", number(source.ac_phase)),
+                ("waveform", source.waveform.as_ref().map(waveform_json).unwrap_or(Json::Null)),
+            ],
+        )
+    } else if let Some(source) = any.downcast_ref::<CurrentSource>() {
+        (
+            "current_source",
+            vec![
+                ("current", number(source.current)),
+                ("polarity", polarity_json(&source.polarity)),
+                ("ac_magnitude", number(source.ac_magnitude)),
+                ("ac_phase", number(source.ac_phase)),
+                ("waveform", source.waveform.as_ref().map(waveform_json).unwrap_or(Json::Null)),
+            ],
+        )
+    } else if let Some(source) = any.downcast_ref::<Vcvs>() {
+        ("vcvs", vec![("gain", number(source.gain))])
+    } else if let Some(source) = any.downcast_ref::<Vccs>() {
+        ("vccs", vec![("transconductance", number(source.transconductance))])
+    } else if let Some(source) = any.downcast_ref::<Ccvs>() {
+        ("ccvs", vec![("transresistance", number(source.transresistance)), ("control", string(&source.control))])
+    } else if let Some(source) = any.downcast_ref::<Cccs>() {
+        ("cccs", vec![("gain", number(source.gain)), ("control", string(&source.control))])
+    } else if let Some(op_amp) = any.downcast_ref::<OpAmp>() {
+        let rails = op_amp.rails.map(|(negative, positive)| numbers(&[negative, positive])).unwrap_or(Json::Null);
+        (
+            "op_amp",
+            vec![
+                ("gain", optional(op_amp.gain)),
+                ("input_resistance", optional(op_amp.input_resistance)),
+                ("output_resistance", number(op_amp.output_resistance)),
+                ("gain_bandwidth", optional(op_amp.gain_bandwidth)),
+                ("rails", rails),
+            ],
+        )
+    } else if let Some(diode) = any.downcast_ref::<Diode>() {
+        (
+            "diode",
+            vec![
+                ("saturation_current", number(diode.saturation_current)),
+                ("emission_coefficient", number(diode.emission_coefficient)),
+                ("series_resistance", number(diode.series_resistance)),
+                ("breakdown_voltage", optional(diode.breakdown_voltage)),
+            ],
+        )
+    } else if let Some(bjt) = any.downcast_ref::<Bjt>() {
+        let bjt_type = match bjt.bjt_type {
+            BjtType::Npn => "npn",
+            BjtType::Pnp => "pnp",
+        };
+        (
+            "bjt",
+            vec![
+                ("bjt_type", string(bjt_type)),
+                ("saturation_current", number(bjt.saturation_current)),
+                ("forward_beta", number(bjt.forward_beta)),
+                ("reverse_beta", number(bjt.reverse_beta)),
+                ("early_voltage", optional(bjt.early_voltage)),
+            ],
+        )
+    } else if let Some(mosfet) = any.downcast_ref::<Mosfet>() {
+        let mosfet_type = match mosfet.mosfet_type {
+            MosfetType::Nmos => "nmos",
+            MosfetType::Pmos => "pmos",
+        };
+        (
+            "mosfet",
+            vec![
+                ("mosfet_type", string(mosfet_type)),
+                ("threshold_voltage", number(mosfet.threshold_voltage)),
+                ("transconductance", number(mosfet.transconductance)),
+                ("channel_length_modulation", number(mosfet.channel_length_modulation)),
+                ("width", number(mosfet.width)),
+                ("length", number(mosfet.length)),
+                ("body_effect", number(mosfet.body_effect)),
+                ("surface_potential", number(mosfet.surface_potential)),
+            ],
+        )
+    } else {
+        return Err(JsonError::UnsupportedComponent(base.name.clone()));
+    };
+
+    // the solved values are kept apart from parameters such as a source's voltage
+    let result = if base.voltage.is_some() || base.current.is_some() {
+        object(vec![("voltage", optional(base.voltage)), ("current", optional(base.current))])
+    } else {
+        Json::Null
+    };
+    let nodes: Vec<f64> = base.nodes.iter().map(|&node| node as f64).collect();
+    let mut fields = vec![("type", string(kind)), ("name", string(&base.name)), ("nodes", numbers(&nodes))];
+    fields.extend(parameters);
+    fields.push(("result", result));
+    Ok(object(fields))
+}
+
+// Model parameters that are left out keep the defaults of the component's constructor.
+fn read_component(fields: &Fields) -> Result<Box<dyn Component>, JsonError> {
+    let name = fields.string("name")?;
+    let mut component: Box<dyn Component> = match fields.string("type")? {
+        "resistor" => Box::new(Resistor::new(name, fields.number("resistance")?)),
+        "capacitor" => {
+            let mut capacitor = Capacitor::new(name, fields.number("capacitance")?);
+            capacitor.initial_voltage = fields.optional_number("initial_voltage")?;
+            Box::new(capacitor)
+        }
+        "inductor" => {
+            let mut inductor = Inductor::new(name, fields.number("inductance")?);
+            inductor.initial_current = fields.optional_number("initial_current")?;
+            Box::new(inductor)
+        }
+        "voltage_source" => {
+            let mut source = VoltageSource::new(name, fields.number("voltage")?, read_polarity(fields)?);
+            fields.set("ac_magnitude", &mut source.ac_magnitude)?;
+            fields.set("ac_phase", &mut source.ac_phase)?;
+            source.waveform = read_waveform(fields)?;
+            Box::new(source)
+        }
+        "current_source" => {
+            let mut source = CurrentSource::new(name, fields.number("current")?, read_polarity(fields)?);
+            fields.set("ac_magnitude", &mut source.ac_magnitude)?;
+            fields.set("ac_phase", &mut source.ac_phase)?;
+            source.waveform = read_waveform(fields)?;
+            Box::new(source)
+        }
+        "vcvs" => Box::new(Vcvs::new(name, fields.number("gain")?)),
+        "vccs" => Box::new(Vccs::new(name, fields.number("transconductance")?)),
+        "ccvs" => Box::new(Ccvs::new(name, fields.number("transresistance")?, fields.string("control")?)),
+        "cccs" => Box::new(Cccs::new(name, fields.number("gain")?, fields.string("control")?)),
+        "op_amp" => {
+            let mut op_amp = match fields.optional_number("gain")? {
+                Some(gain) => OpAmp::new(name, gain),
+                None => OpAmp::ideal(name),
+            };
+            op_amp.input_resistance = fields.optional_number("input_resistance")?;
+            fields.set("output_resistance", &mut op_amp.output_resistance)?;
+            op_amp.gain_bandwidth = fields.optional_number("gain_bandwidth")?;
+            if fields.optional("rails").is_some() {
+                match fields.numbers("rails")?.as_slice() {
+                    [negative, positive] => op_amp.rails = Some((*negative, *positive)),
+                    _ => return Err(JsonError::Schema(format!("{} must hold two numbers", fields.context("rails")))),
+                }
+            }
+            Box::new(op_amp)
+        }
+        "diode" => {
+            let mut diode = Diode::new(name);
+            fields.set("saturation_current", &mut diode.saturation_current)?;
+            fields.set("emission_coefficient", &mut diode.emission_coefficient)?;
+            fields.set("series_resistance", &mut diode.series_resistance)?;
+            diode.breakdown_voltage = fields.optional_number("breakdown_voltage")?;
+            Box::new(diode)
+        }
+        "bjt" => {
+            let bjt_type = match fields.string("bjt_type")? {
+                "npn" => BjtType::Npn,
+                "pnp" => BjtType::Pnp,
+                other => return Err(JsonError::Schema(format!("{} must be npn or pnp, not {}", fields.context("bjt_type"), other))),
+            };
+            let mut bjt = Bjt::new(name, bjt_type);
+            fields.set("saturation_current", &mut bjt.saturation_current)?;
+            fields.set("forward_beta", &mut bjt.forward_beta)?;
+            fields.set("reverse_beta", &mut bjt.reverse_beta)?;
+            bjt.early_voltage = fields.optional_number("early_voltage")?;
+            Box::new(bjt)
+        }
+        "mosfet" => {
+            let mosfet_type = match fields.string("mosfet_type")? {
+                "nmos" => MosfetType::Nmos,
+                "pmos" => MosfetType::Pmos,
+                other => return Err(JsonError::Schema(format!("{} must be nmos or pmos, not {}", fields.context("mosfet_type"), other))),
+            };
+            let mut mosfet = Mosfet::new(name, mosfet_type);
+            fields.set("threshold_voltage", &mut mosfet.threshold_voltage)?;
+            fields.set("transconductance", &mut mosfet.transconductance)?;
+            fields.set("channel_length_modulation", &mut mosfet.channel_length_modulation)?;
+            fields.set("width", &mut mosfet.width)?;
+            fields.set("length", &mut mosfet.length)?;
+            fields.set("body_effect", &mut mosfet.body_effect)?;
+            fields.set("surface_potential", &mut mosfet.surface_potential)?;
+            Box::new(mosfet)
+        }
+        other => return Err(JsonError::Schema(format!("{} is not a known component type", other))),
+    };
+
+    let context = fields.context("nodes");
+    let nodes = fields
+        .array("nodes")?
+        .iter()
+        .enumerate()
+        .map(|(i, node)| read_index(node, &format!("{}[{}]", context, i)))
+        .collect::<Result<Vec<usize>, JsonError>>()?;
+    if nodes.len() != component.terminals().len() {
+        return Err(JsonError::Schema(format!("{} has {} nodes for {} terminals", name, nodes.len(), component.terminals().len())));
+    }
+
+    let base = component.component_mut();
+    base.nodes = nodes;
+    if let Some(result) = fields.optional("result") {
+        let result = Fields::new(result, fields.context("result"))?;
+        base.voltage = result.optional_number("voltage")?;
+        base.current = result.optional_number("current")?;
+    }
+    Ok(component)
+}
+
+fn polarity_json(polarity: &Polarity) -> Json {
+    string(match polarity {
+        Polarity::Normal => "normal",
+        Polarity::Inverted => "inverted",
+    })
+}
+
+fn read_polarity(fields: &Fields) -> Result<Polarity, JsonError> {
+    match fields.optional("polarity").map(|_| fields.string("polarity")).transpose()? {
+        None | Some("normal") => Ok(Polarity::Normal),
+        Some("inverted") => Ok(Polarity::Inverted),
+        Some(other) => Err(JsonError::Schema(format!("{} must be normal or inverted, not {}", fields.context("polarity"), other))),
+    }
+}
+
+fn waveform_json(waveform: &Waveform) -> Json {
+    match waveform {
+        Waveform::Sin { offset, amplitude, frequency, delay, damping, phase } => object(vec![
+            ("type", string("sin")),
+            ("offset", number(*offset)),
+            ("amplitude", number(*amplitude)),
+            ("frequency", number(*frequency)),
+            ("delay", number(*delay)),
+            ("damping", number(*damping)),
+            ("phase", number(*phase)),
+        ]),
+        Waveform::Pulse { initial, pulsed, delay, rise, fall, width, period } => object(vec![
+            ("type", string("pulse")),
+            ("initial", number(*initial)),
+            ("pulsed", number(*pulsed)),
+            ("delay", number(*delay)),
+            ("rise", number(*rise)),
+            ("fall", number(*fall)),
+            ("width", number(*width)),
+            ("period", optional(*period)),
+        ]),
+        Waveform::Pwl(points) => object(vec![
+            ("type", string("pwl")),
+            ("points", Json::Array(points.iter().map(|(time, value)| numbers(&[*time, *value])).collect())),
+        ]),
+        Waveform::Exp { initial, pulsed, rise_delay, rise_time_constant, fall_delay, fall_time_constant } => object(vec![
+            ("type", string("exp")),
+            ("initial", number(*initial)),
+            ("pulsed", number(*pulsed)),
+            ("rise_delay", number(*rise_delay)),
+            ("rise_time_constant", number(*rise_time_constant)),
+            ("fall_delay", number(*fall_delay)),
+            ("fall_time_constant", number(*fall_time_constant)),
+        ]),
+    }
+}
+
+fn read_waveform(component: &Fields) -> Result<Option<Waveform>, JsonError> {
+    let Some(value) = component.optional("waveform") else {
+        return Ok(None);
+    };
+    let fields = Fields::new(value, component.context("waveform"))?;
+    let waveform = match fields.string("type")? {
+        "sin" => Waveform::Sin {
+            offset: fields.number("offset")?,
+            amplitude: fields.number("amplitude")?,
+            frequency: fields.number("frequency")?,
+            delay: fields.number("delay")?,
+            damping: fields.number("damping")?,
+            phase: fields.number("phase")?,
+        },
+        "pulse" => Waveform::Pulse {
+            initial: fields.number("initial")?,
+            pulsed: fields.number("pulsed")?,
+            delay: fields.number("delay")?,
+            rise: fields.number("rise")?,
+            fall: fields.number("fall")?,
+            width: fields.number("width")?,
+            period: fields.optional_number("period")?,
+        },
+        "pwl" => {
+            let context = fields.context("points");
+            let points = fields
+                .array("points")?
+                .iter()
+                .enumerate()
+                .map(|(i, point)| match read_numbers(point, &format!("{}[{}]", context, i))?.as_slice() {
+                    [time, value] => Ok((*time, *value)),
+                    _ => Err(JsonError::Schema(format!("{}[{}] must hold a time and a value", context, i))),
+                })
+                .collect::<Result<Vec<(f64, f64)>, JsonError>>()?;
+            Waveform::Pwl(points)
+        }
+        "exp" => Waveform::Exp {
+            initial: fields.number("initial")?,
+            pulsed: fields.number("pulsed")?,
+            rise_delay: fields.number("rise_delay")?,
+            rise_time_constant: fields.number("rise_time_constant")?,
+            fall_delay: fields.number("fall_delay")?,
+            fall_time_constant: fields.number("fall_time_constant")?,
+        },
+        other => return Err(JsonError::Schema(format!("{} is not a known waveform", other))),
+    };
+    Ok(Some(waveform))
+}
+
+// Solve results
+impl AcSolution {
+    pub fn to_json(&self) -> String {
+        document(
+            "ac_solution",
+            vec![
+                ("frequency", number(self.frequency)),
+                ("node_voltages", Json::Array(self.node_voltages.iter().map(|value| complex(*value)).collect())),
+                ("voltages", map(&self.voltages, |value| complex(*value))),
+                ("currents", map(&self.currents, |value| complex(*value))),
+            ],
+        )
+    }
+
+    pub fn from_json(text: &str) -> Result<AcSolution, JsonError> {
+        let json = Json::parse(text)?;
+        let document = read_document(&json, "ac_solution")?;
+        let frequency = document.number("frequency")?;
+        if frequency < 0.0 {
+            return Err(JsonError::Schema("frequency must be a non-negative number".to_string()));
+        }
+        let voltages = document.map("voltages", read_complex)?;
+        let currents = document.map("currents", read_complex)?;
+        check_currents(&voltages, &currents)?;
+        Ok(AcSolution {
+            frequency,
+            node_voltages: read_complexes(document.get("node_voltages")?, "node_voltages")?,
+            voltages,
+            currents,
+        })
+    }
+}
+
+impl AcSweep {
+    pub fn to_json(&self) -> String {
+        let probes = self
+            .probes
+            .iter()
+            .map(|probe| match probe {
+                Probe::Node(node) => object(vec![("node", number(*node as f64))]),
+                Probe::Voltage(name) => object(vec![("voltage", string(name))]),
+                Probe::Current(name) => object(vec![("current", string(name))]),
+            })
+            .collect();
+        let values = self
+            .values
+            .iter()
+            .map(|values| Json::Array(values.iter().map(|value| complex(*value)).collect()))
+            .collect();
+        document(
+            "ac_sweep",
+            vec![("frequencies", numbers(&self.frequencies)), ("probes", Json::Array(probes)), ("values", Json::Array(values))],
+        )
+    }
+
+    pub fn from_json(text: &str) -> Result<AcSweep, JsonError> {
+        let json = Json::parse(text)?;
+        let document = read_document(&json, "ac_sweep")?;
+
+        let mut probes = Vec::new();
+        for (i, value) in document.array("probes")?.iter().enumerate() {
+            let fields = Fields::new(value, format!("probes[{}]", i))?;
+            let probe = if fields.optional("node").is_some() {
+                Probe::Node(fields.index("node")?)
+            } else if fields.optional("voltage").is_some() {
+                Probe::Voltage(fields.string("voltage")?.to_string())
+            } else if fields.optional("current").is_some() {
+                Probe::Current(fields.string("current")?.to_string())
+            } else {
+                return Err(JsonError::Schema(format!("probes[{}] must have a node, voltage or current", i)));
+            };
+            probes.push(probe);
+        }
+
+        let values = document
+            .array("values")?
+            .iter()
+            .enumerate()
+            .map(|(i, values)| read_complexes(values, &format!("values[{}]", i)))
+            .collect::<Result<Vec<Vec<Complex>>, JsonError>>()?;
+
+        // one row of values per probe, each with a value per frequency
+        let frequencies = document.numbers("frequencies")?;
+        if values.len() != probes.len() {
+            return Err(JsonError::Schema(format!("values has {} rows for {} probes", values.len(), probes.len())));
+        }
+        check_lengths(values.iter().map(Vec::len), frequencies.len(), "values", "frequencies")?;
+
+        Ok(AcSweep {
+            frequencies,
+            probes,
+            values,
+        })
+    }
+}
+
+impl DcSweep {
+    pub fn to_json(&self) -> String {
+        let sources = Json::Array(self.sources.iter().map(|source| string(source)).collect());
+        document(
+            "dc_sweep",
+            vec![
+                ("sources", sources),
+                ("source_values", table(&self.source_values)),
+                ("node_voltages", optional_table(&self.node_voltages)),
+                ("voltages", map(&self.voltages, |values| numbers(values))),
+                ("currents", map(&self.currents, |values| numbers(values))),
+            ],
+        )
+    }
+
+    pub fn from_json(text: &str) -> Result<DcSweep, JsonError> {
+        let json = Json::parse(text)?;
+        let document = read_document(&json, "dc_sweep")?;
+        let sources = document
+            .array("sources")?
+            .iter()
+            .enumerate()
+            .map(|(i, source)| read_string(source, &format!("sources[{}]", i)).map(str::to_string))
+            .collect::<Result<Vec<String>, JsonError>>()?;
+
+        // a value per swept source at every point, and a value per point in every series
+        let source_values = document.table("source_values")?;
+        check_lengths(source_values.iter().map(Vec::len), sources.len(), "source_values", "sources")?;
+        let node_voltages = document.optional_table("node_voltages")?;
+        check_lengths(node_voltages.iter().map(Vec::len), source_values.len(), "node_voltages", "source_values")?;
+        let voltages = document.map("voltages", read_numbers)?;
+        check_lengths(voltages.values().map(Vec::len), source_values.len(), "voltages", "source_values")?;
+        let currents = document.map("currents", read_numbers)?;
+        check_lengths(currents.values().map(Vec::len), source_values.len(), "currents", "source_values")?;
+
+        Ok(DcSweep {
+            sources,
+            source_values,
+            node_voltages,
+            voltages,
+            currents,
+        })
+    }
+}
+
+// every row of a table must have as many entries as another list
+fn check_lengths(rows: impl Iterator<Item = usize>, expected: usize, table: &str, list: &str) -> Result<(), JsonError> {
+    for length in rows {
+        if length != expected {
+            return Err(JsonError::Schema(format!("{} has a row of {} values for {} {}", table, length, expected, list)));
+        }
+    }
+    Ok(())
+}
+
+// every component with a current also has a voltage
+fn check_currents<T, U>(voltages: &HashMap<String, T>, currents: &HashMap<String, U>) -> Result<(), JsonError> {
+    let mut names: Vec<&String> = currents.keys().filter(|name| !voltages.contains_key(*name)).collect();
+    names.sort();
+    match names.first() {
+        Some(name) => Err(JsonError::Schema(format!("currents.{} is given for a component without a voltage", name))),
+        None => Ok(()),
+    }
+}
+
+impl TransientResult {
+    pub fn to_json(&self) -> String {
+        document(
+            "transient",
+            vec![
+                ("times", numbers(&self.times)),
+                ("node_voltages", table(&self.node_voltages)),
+                ("voltages", map(&self.voltages, |values| numbers(values))),
+                ("currents", map(&self.currents, |values| numbers(values))),
+            ],
+        )
+    }
+
+    pub fn from_json(text: &str) -> Result<TransientResult, JsonError> {
+        let json = Json::parse(text)?;
+        let document = read_document(&json, "transient")?;
+
+        // a value per time point in every row and series
+        let times = document.numbers("times")?;
+        let node_voltages = document.table("node_voltages")?;
+        check_lengths(node_voltages.iter().map(Vec::len), times.len(), "node_voltages", "times")?;
+        let voltages = document.map("voltages", read_numbers)?;
+        check_lengths(voltages.values().map(Vec::len), times.len(), "voltages", "times")?;
+        let currents = document.map("currents", read_numbers)?;
+        check_lengths(currents.values().map(Vec::len), times.len(), "currents", "times")?;
+        check_currents(&voltages, &currents)?;
+
+        Ok(TransientResult {
+            times,
+            node_voltages,
+            voltages,
+            currents,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one of every component kind, with parameters away from their defaults
+    fn every_component() -> Circuit {
+        let mut circuit = Circuit::new();
+        let mut capacitor = Capacitor::new("C1", 1e-6);
+        capacitor.initial_voltage = Some(2.0);
+        let mut inductor = Inductor::new("L1", 1e-3);
+        inductor.initial_current = Some(0.5);
+        let mut voltage_source = VoltageSource::new("V1", 10.0, Polarity::Inverted);
+        voltage_source.ac_magnitude = 1.0;
+        voltage_source.ac_phase = 90.0;
+        voltage_source.waveform = Some(Waveform::Sin { offset: 0.0, amplitude: 1.0, frequency: 1e3, delay: 0.0, damping: 0.0, phase: 0.0 });
+        let mut current_source = CurrentSource::new("I1", 1.0, Polarity::Normal);
+        current_source.waveform = Some(Waveform::Pulse {
+            initial: 0.0,
+            pulsed: 1.0,
+            delay: 0.0,
+            rise: 1e-9,
+            fall: 1e-9,
+            width: f64::INFINITY,
+            period: None,
+        });
+        let mut op_amp = OpAmp::new("U1", 1e5);
+        op_amp.input_resistance = Some(1e6);
+        op_amp.output_resistance = 75.0;
+        op_amp.gain_bandwidth = Some(1e6);
+        op_amp.rails = Some((-12.0, 12.0));
+        let mut diode = Diode::new("D1");
+        diode.breakdown_voltage = Some(5.1);
+        let mut bjt = Bjt::new("Q1", BjtType::Pnp);
+        bjt.early_voltage = Some(80.0);
+        let mut mosfet = Mosfet::new("M1", MosfetType::Pmos);
+        mosfet.width = 10e-6;
+
+        let mut pwl = VoltageSource::new("V2", 0.0, Polarity::Normal);
+        pwl.waveform = Some(Waveform::Pwl(vec![(0.0, 0.0), (1e-3, 1.0)]));
+        let mut exp = CurrentSource::new("I2", 0.0, Polarity::Inverted);
+        exp.waveform = Some(Waveform::Exp {
+            initial: 0.0,
+            pulsed: 1.0,
+            rise_delay: 0.0,
+            rise_time_constant: 1e-4,
+            fall_delay: f64::INFINITY,
+            fall_time_constant: 1e-4,
+        });
+
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        circuit.add_component(capacitor).unwrap();
+        circuit.add_component(inductor).unwrap();
+        circuit.add_component(voltage_source).unwrap();
+        circuit.add_component(current_source).unwrap();
+        circuit.add_component(Vcvs::new("E1", 2.0)).unwrap();
+        circuit.add_component(Vccs::new("G1", 1e-3)).unwrap();
+        circuit.add_component(Ccvs::new("H1", 50.0, "V1")).unwrap();
+        circuit.add_component(Cccs::new("F1", 3.0, "V1")).unwrap();
+        circuit.add_component(op_amp).unwrap();
+        circuit.add_component(diode).unwrap();
+        circuit.add_component(bjt).unwrap();
+        circuit.add_component(mosfet).unwrap();
+        circuit.add_component(pwl).unwrap();
+        circuit.add_component(exp).unwrap();
+        for (component, terminal, net) in [
+            ("R1", 1, "a"), ("R1", 2, "0"),
+            ("C1", 1, "a"), ("C1", 2, "b"),
+            ("L1", 1, "b"), ("L1", 2, "0"),
+            ("V1", 1, "a"), ("V1", 2, "0"),
+            ("I1", 1, "b"), ("I1", 2, "c"),
+            ("E1", 1, "c"), ("E1", 2, "0"), ("E1", 3, "a"), ("E1", 4, "b"),
+            ("G1", 1, "d"), ("G1", 2, "0"), ("G1", 3, "a"), ("G1", 4, "b"),
+            ("H1", 1, "e"), ("H1", 2, "0"),
+            ("F1", 1, "e"), ("F1", 2, "0"),
+            ("U1", 1, "f"), ("U1", 2, "0"), ("U1", 3, "a"), ("U1", 4, "b"),
+            ("D1", 1, "a"), ("D1", 2, "d"),
+            ("Q1", 1, "c"), ("Q1", 2, "d"), ("Q1", 3, "e"),
+            ("M1", 1, "f"), ("M1", 2, "e"), ("M1", 3, "d"), ("M1", 4, "0"),
+            ("V2", 1, "g"), ("V2", 2, "0"),
+            ("I2", 1, "g"), ("I2", 2, "0"),
+        ] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        circuit
+    }
+
+    fn schema_error(result: Result<impl Sized, JsonError>) -> String {
+        match result {
+            Err(JsonError::Schema(message)) => message,
+            Err(other) => panic!("expected a schema error, got {}", other),
+            Ok(_) => panic!("expected a schema error"),
+        }
+    }
+
+    #[test]
+    fn round_trips_every_component_kind() {
+        let circuit = every_component();
+        let saved = circuit.to_json().unwrap();
+        let loaded = Circuit::from_json(&saved).unwrap();
+        assert_eq!(loaded.to_json().unwrap(), saved);
+        assert_eq!(loaded.components.len(), 15);
+        for (name, component) in &circuit.components {
+            let other = loaded.get_component(name).unwrap();
+            assert_eq!(other.component().nodes, component.component().nodes);
+            assert_eq!(other.component().voltage, None);
+        }
+
+        let source = loaded.get_component("V1").unwrap().as_any().downcast_ref::<VoltageSource>().unwrap();
+        assert_eq!((source.voltage, source.polarity.sign()), (10.0, -1.0));
+        let source = loaded.get_component("I1").unwrap().as_any().downcast_ref::<CurrentSource>().unwrap();
+        assert_eq!((source.current, source.polarity.sign()), (1.0, 1.0));
+        let op_amp = loaded.get_component("U1").unwrap().as_any().downcast_ref::<OpAmp>().unwrap();
+        assert_eq!(op_amp.rails, Some((-12.0, 12.0)));
+    }
+
+    #[test]
+    fn keeps_results_apart_from_source_parameters() {
+        let mut circuit = every_component();
+        for (i, component) in circuit.components.values_mut().enumerate() {
+            let base = component.component_mut();
+            base.voltage = Some(-(i as f64) - 0.5);
+            base.current = Some(i as f64 * 1e-3);
+        }
+        let saved = circuit.to_json().unwrap();
+        let loaded = Circuit::from_json(&saved).unwrap();
+        assert_eq!(loaded.to_json().unwrap(), saved);
+        for (name, component) in &circuit.components {
+            let (base, other) = (component.component(), loaded.get_component(name).unwrap().component());
+            assert_eq!((other.voltage, other.current), (base.voltage, base.current));
+        }
+        let source = loaded.get_component("V1").unwrap().as_any().downcast_ref::<VoltageSource>().unwrap();
+        assert_eq!(source.voltage, 10.0);
+        let source = loaded.get_component("I1").unwrap().as_any().downcast_ref::<CurrentSource>().unwrap();
+        assert_eq!(source.current, 1.0);
+    }
+
+    #[test]
+    fn solved_sources_reload_with_their_own_values() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 10.0, Polarity::Inverted)).unwrap();
+        circuit.add_component(CurrentSource::new("I1", 1.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        circuit.add_component(Resistor::new("R2", 1.0)).unwrap();
+        for (component, terminal, net) in [("V1", 1, "a"), ("V1", 2, "0"), ("I1", 1, "b"), ("I1", 2, "0"), ("R1", 1, "a"), ("R1", 2, "0"), ("R2", 1, "b"), ("R2", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        circuit.solve_dc().unwrap();
+
+        let mut loaded = Circuit::from_json(&circuit.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.get_component("V1").unwrap().component().voltage, Some(-10.0));
+        loaded.solve_dc().unwrap();
+        assert_eq!(loaded.net_voltage("a"), circuit.net_voltage("a"));
+        assert_eq!(loaded.net_voltage("b"), circuit.net_voltage("b"));
+    }
+
+    #[test]
+    fn rejects_repeated_keys() {
+        let text = r#"{"version": 1, "kind": "circuit", "nodes": [], "wires": [], "components": [], "wires": []}"#;
+        assert_eq!(schema_error(Circuit::from_json(text)), "document.wires is given more than once");
+    }
+
+    #[test]
+    fn rejects_other_versions_and_kinds() {
+        let text = r#"{"version": 2, "kind": "circuit", "nodes": [], "wires": [], "components": []}"#;
+        assert_eq!(Circuit::from_json(text).err(), Some(JsonError::UnsupportedVersion(2)));
+        let text = r#"{"version": 1, "kind": "transient", "nodes": [], "wires": [], "components": []}"#;
+        assert_eq!(schema_error(Circuit::from_json(text)), "expected a circuit document, found transient");
+    }
+
+    #[test]
+    fn parses_values_and_escapes() {
+        let json = Json::parse(r#" {"a": [1, -2.5e3, true, false, null], "b": "\"\\\/\n\u00e9\ud83d\ude00"} "#).unwrap();
+        let expected = Json::Object(vec![
+            (
+                "a".to_string(),
+                Json::Array(vec![Json::Number(1.0), Json::Number(-2500.0), Json::Bool(true), Json::Bool(false), Json::Null]),
+            ),
+            ("b".to_string(), Json::String("\"\\/\né😀".to_string())),
+        ]);
+        assert_eq!(json, expected);
+        assert_eq!(Json::parse(&json.to_string_pretty()).unwrap(), json);
+    }
+
+    #[test]
+    fn writes_numbers_json_cannot_hold_as_strings() {
+        let text = numbers(&[f64::INFINITY, f64::NEG_INFINITY, f64::NAN, 1e-12, 0.1]).to_string_pretty();
+        assert_eq!(text, "[\"inf\", \"-inf\", \"nan\", 1e-12, 0.1]\n");
+        let values = read_numbers(&Json::parse(&text).unwrap(), "values").unwrap();
+        assert_eq!(values[..2], [f64::INFINITY, f64::NEG_INFINITY]);
+        assert!(values[2].is_nan());
+        assert_eq!(values[3..], [1e-12, 0.1]);
+    }
+
+    #[test]
+    fn reports_the_line_and_column_of_syntax_errors() {
+        let cases = [
+            ("{\n  \"a\": 1,\n  \"b\" 2\n}", 3, 7, "expected ':'"),
+            ("[1, 2", 1, 6, "expected ',' or ']'"),
+            ("{\"a\": .5}", 1, 7, "expected a value"),
+            ("\"\\q\"", 1, 3, "invalid escape \\q"),
+            ("[1] 2", 1, 5, "unexpected text after the value"),
+            ("tru", 1, 1, "expected a value"),
+            ("[01]", 1, 2, "01 is not a number"),
+            ("-01", 1, 1, "-01 is not a number"),
+            ("1.", 1, 1, "1. is not a number"),
+            ("1.e5", 1, 1, "1.e5 is not a number"),
+            ("1e+", 1, 1, "1e+ is not a number"),
+            ("\"\\ud83d\\u0041\"", 1, 8, "unpaired surrogate in unicode escape"),
+            ("\"\\ud83dx\"", 1, 8, "unpaired surrogate in unicode escape"),
+        ];
+        for (text, line, column, message) in cases {
+            let expected = JsonError::Syntax { line, column, message: message.to_string() };
+            assert_eq!(Json::parse(text).err(), Some(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn limits_how_deeply_values_nest() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        let expected = JsonError::Syntax { line: 1, column: MAX_DEPTH + 1, message: "arrays and objects nest more than 128 deep".to_string() };
+        assert_eq!(Json::parse(&nested(MAX_DEPTH + 1)).err(), Some(expected));
+        // the limit counts objects as well as arrays
+        let text = format!("{}{}", r#"{"a": "#.repeat(MAX_DEPTH + 1), "}".repeat(MAX_DEPTH + 1));
+        assert!(Json::parse(&text).is_err());
+    }
+
+    #[test]
+    fn round_trips_sweep_and_transient_results() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 1.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        circuit.add_component(Capacitor::new("C1", 1e-6)).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("R1", 1, "in"), ("R1", 2, "out"), ("C1", 1, "out"), ("C1", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        circuit.get_component_mut("V1").unwrap().as_any_mut().downcast_mut::<VoltageSource>().unwrap().ac_magnitude = 1.0;
+
+        let solution = circuit.solve_ac(1e3).unwrap();
+        assert_eq!(AcSolution::from_json(&solution.to_json()).unwrap().to_json(), solution.to_json());
+
+        let out = circuit.net_node("out").unwrap();
+        let sweep = circuit.ac_sweep(10.0, 1e4, 7, crate::ac::SweepScale::Decade, &[Probe::Node(out), Probe::Current("R1".to_string())]).unwrap();
+        assert_eq!(AcSweep::from_json(&sweep.to_json()).unwrap().to_json(), sweep.to_json());
+
+        let sweep = circuit.dc_sweep("V1", 0.0, 1.0, 0.25).unwrap();
+        assert_eq!(DcSweep::from_json(&sweep.to_json()).unwrap().to_json(), sweep.to_json());
+
+        let result = circuit.simulate_transient(1e-3, 1e-4).unwrap();
+        assert_eq!(TransientResult::from_json(&result.to_json()).unwrap().to_json(), result.to_json());
+    }
+
+    #[test]
+    fn rejects_results_with_mismatched_shapes() {
+        let dc = |source_values: &str| {
+            format!(
+                r#"{{"version": 1, "kind": "dc_sweep", "sources": ["V1", "V2"], "source_values": {}, "node_voltages": [], "voltages": {{}}, "currents": {{}}}}"#,
+                source_values
+            )
+        };
+        assert!(DcSweep::from_json(&dc("[[0, 1], [1, 2]]")).is_ok());
+        assert_eq!(schema_error(DcSweep::from_json(&dc("[[0, 1], [1]]"))), "source_values has a row of 1 values for 2 sources");
+
+        let ac = |values: &str| {
+            format!(
+                r#"{{"version": 1, "kind": "ac_sweep", "frequencies": [1, 10], "probes": [{{"node": 1}}], "values": {}}}"#,
+                values
+            )
+        };
+        let point = r#"{"re": 1, "im": 0}"#;
+        assert!(AcSweep::from_json(&ac(&format!("[[{0}, {0}]]", point))).is_ok());
+        assert_eq!(schema_error(AcSweep::from_json(&ac("[]"))), "values has 0 rows for 1 probes");
+        assert_eq!(schema_error(AcSweep::from_json(&ac(&format!("[[{}]]", point)))), "values has a row of 1 values for 2 frequencies");
+
+        let solution = |frequency: &str, currents: &str| {
+            format!(
+                r#"{{"version": 1, "kind": "ac_solution", "frequency": {}, "node_voltages": [], "voltages": {{"R1": {}}}, "currents": {}}}"#,
+                frequency, point, currents
+            )
+        };
+        assert!(AcSolution::from_json(&solution("1000", &format!(r#"{{"R1": {}}}"#, point))).is_ok());
+        assert_eq!(schema_error(AcSolution::from_json(&solution("-1", "{}"))), "frequency must be a non-negative number");
+        let currents = format!(r#"{{"R1": {0}, "R2": {0}}}"#, point);
+        assert_eq!(schema_error(AcSolution::from_json(&solution("1000", &currents))), "currents.R2 is given for a component without a voltage");
+
+        let transient = |node_voltages: &str, voltages: &str, currents: &str| {
+            format!(
+                r#"{{"version": 1, "kind": "transient", "times": [0, 1], "node_voltages": {}, "voltages": {}, "currents": {}}}"#,
+                node_voltages, voltages, currents
+            )
+        };
+        assert!(TransientResult::from_json(&transient("[[0, 1]]", r#"{"R1": [0, 1]}"#, r#"{"R1": [0, 1]}"#)).is_ok());
+        let error = schema_error(TransientResult::from_json(&transient("[[0, 1], [0]]", "{}", "{}")));
+        assert_eq!(error, "node_voltages has a row of 1 values for 2 times");
+        let error = schema_error(TransientResult::from_json(&transient("[]", r#"{"R1": [0, 1, 2]}"#, "{}")));
+        assert_eq!(error, "voltages has a row of 3 values for 2 times");
+        let error = schema_error(TransientResult::from_json(&transient("[]", r#"{"R1": [0, 1]}"#, r#"{"R1": [0]}"#)));
+        assert_eq!(error, "currents has a row of 1 values for 2 times");
+        let error = schema_error(TransientResult::from_json(&transient("[]", "{}", r#"{"R1": [0, 1]}"#)));
+        assert_eq!(error, "currents.R1 is given for a component without a voltage");
+    }
+}
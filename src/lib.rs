@@ -0,0 +1,19 @@
+// src/lib.rs
+
+pub mod ac;
+pub mod builder;
+pub mod complex;
+pub mod dc;
+pub mod equivalent;
+pub mod error;
+pub mod json;
+pub mod matrix;
+mod mna;
+pub mod nets;
+pub mod reduction;
+pub mod spice;
+pub mod stamp;
+pub mod transient;
+pub mod types;
+pub mod validation;
+pub mod waveform;
@@ -1,26 +1,31 @@
 // src/main.rs
 
-mod types;
-
-use types::{Circuit, VoltageSource, Polarity, Resistor};
+use circuit_solver::error::CircuitError;
+use circuit_solver::types::{Circuit, VoltageSource, Polarity, Resistor};
 
 fn main() {
-    
+    if let Err(e) = run() {
+        println!("{}", e);
+    }
+}
+
+fn run() -> Result<(), CircuitError> {
     // create a basic voltage source resistor circuit
     let mut circuit = Circuit::new();
 
     // add voltage source and resistor
-    let voltage_source = VoltageSource::new("V1", 5.0, Polarity::Normal);
-    let resistor: Resistor = Resistor::new("R1", 100.0);
+    circuit.add_component(VoltageSource::new("V1", 5.0, Polarity::Normal))?;
+    circuit.add_component(Resistor::new("R1", 100.0))?;
 
-    circuit.add_component(voltage_source);
-    circuit.add_component(resistor);
+    // connect the two components through named nets
+    circuit.attach("V1", 1, "vin")?;
+    circuit.attach("V1", 2, "gnd")?;
+    circuit.attach("R1", 1, "vin")?;
+    circuit.attach("R1", 2, "gnd")?;
 
-    // connect the two components
-    let voltage_source_ref = circuit.get_component("V1").unwrap();
-    let resistor_ref = circuit.get_component("R1").unwrap();
-    match circuit.connect(voltage_source_ref.component().node1.unwrap(), resistor_ref.component().node2.unwrap()) {
-        Ok(_) => (),
-        Err(e) => println!("{}", e)
-    }
-}
\ No newline at end of file
+    circuit.solve_dc()?;
+    println!("vin = {} V", circuit.net_voltage("vin").unwrap_or_default());
+    println!("I(R1) = {} A", circuit.get_component("R1").unwrap().component().current.unwrap_or_default());
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+// src/matrix.rs
+
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use crate::complex::Complex;
+
+// Values the linear system can be built from (real for DC, complex for AC)
+pub trait Scalar:
+    Copy
+    + PartialEq
+    + From<f64>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + Sum
+{
+    fn magnitude(&self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+}
+
+impl Scalar for Complex {
+    fn magnitude(&self) -> f64 {
+        Complex::magnitude(self)
+    }
+}
+
+// Dense linear system (A * x = b) used by the circuit solvers.
+// Rows and columns are addressed with Option<usize> so that stamps touching
+// the reference node (which has no unknown) can be written without special cases.
+pub struct LinearSystem<T: Scalar = f64> {
+    pub size: usize,
+    pub a: Vec<Vec<T>>,
+    pub b: Vec<T>,
+}
+impl<T: Scalar> LinearSystem<T> {
+    pub fn new(size: usize) -> Self {
+        let zero = T::from(0.0);
+        Self {
+            size,
+            a: vec![vec![zero; size]; size],
+            b: vec![zero; size],
+        }
+    }
+
+    pub fn add(&mut self, row: Option<usize>, col: Option<usize>, value: T) {
+        if let (Some(row), Some(col)) = (row, col) {
+            self.a[row][col] += value;
+        }
+    }
+
+    pub fn add_rhs(&mut self, row: Option<usize>, value: T) {
+        if let Some(row) = row {
+            self.b[row] += value;
+        }
+    }
+
+    // stamp a conductance (or admittance) between two nodes
+    pub fn stamp_conductance(&mut self, n1: Option<usize>, n2: Option<usize>, g: T) {
+        self.add(n1, n1, g);
+        self.add(n2, n2, g);
+        self.add(n1, n2, -g);
+        self.add(n2, n1, -g);
+    }
+
+    // stamp a current of `current` amps injected into node `to` and drawn from node `from`
+    pub fn stamp_current(&mut self, from: Option<usize>, to: Option<usize>, current: T) {
+        self.add_rhs(from, -current);
+        self.add_rhs(to, current);
+    }
+
+    // stamp an ideal voltage branch V(plus) - V(minus) = voltage, with its current
+    // (flowing from plus to minus through the branch) as unknown `branch`
+    pub fn stamp_voltage(&mut self, plus: Option<usize>, minus: Option<usize>, branch: usize, voltage: T) {
+        let one = T::from(1.0);
+        let branch = Some(branch);
+        self.add(plus, branch, one);
+        self.add(minus, branch, -one);
+        self.add(branch, plus, one);
+        self.add(branch, minus, -one);
+        self.add_rhs(branch, voltage);
+    }
+
+    // solve the system using gaussian elimination with partial pivoting
+    // if the matrix is singular, the column of the unknown that could not be solved for is returned
+    pub fn solve(&self) -> Result<Vec<T>, usize> {
+        let n = self.size;
+        let zero = T::from(0.0);
+        let mut a = self.a.clone();
+        let mut b = self.b.clone();
+
+        // scale every row so that its largest entry is 1, which makes the singularity threshold
+        // relative to each equation (teraohm resistors stamp conductances far below 1e-12)
+        for (row, rhs) in a.iter_mut().zip(b.iter_mut()) {
+            let scale = row.iter().fold(0.0_f64, |max, v| max.max(v.magnitude()));
+            if scale > 0.0 {
+                let scale = T::from(scale);
+                for value in row.iter_mut() {
+                    *value = *value / scale;
+                }
+                *rhs = *rhs / scale;
+            }
+        }
+        let epsilon = 1e-12;
+
+        for col in 0..n {
+            // find the row with the largest pivot
+            let pivot = (col..n)
+                .max_by(|&i, &j| a[i][col].magnitude().total_cmp(&a[j][col].magnitude()))
+                .unwrap();
+            if a[pivot][col].magnitude() < epsilon {
+                return Err(col);
+            }
+            a.swap(col, pivot);
+            b.swap(col, pivot);
+
+            // eliminate the column below the pivot
+            let pivot_row = a[col].clone();
+            for row in (col + 1)..n {
+                let factor = a[row][col] / pivot_row[col];
+                if factor == zero {
+                    continue;
+                }
+                for (value, pivot) in a[row].iter_mut().zip(&pivot_row).skip(col) {
+                    *value -= factor * *pivot;
+                }
+                let pivot_rhs = b[col];
+                b[row] -= factor * pivot_rhs;
+            }
+        }
+
+        // back substitution
+        let mut x = vec![zero; n];
+        for row in (0..n).rev() {
+            let sum: T = ((row + 1)..n).map(|k| a[row][k] * x[k]).sum();
+            x[row] = (b[row] - sum) / a[row][row];
+        }
+
+        Ok(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * b.abs().max(1e-12)
+    }
+
+    #[test]
+    fn solves_teraohm_circuits() {
+        // I1 0 a 1p; R1 a 0 2T
+        let mut system = LinearSystem::new(1);
+        system.stamp_conductance(Some(0), None, 1.0 / 2e12);
+        system.stamp_current(None, Some(0), 1e-12);
+        assert!(close(system.solve().unwrap()[0], 2.0));
+
+        // V1 a 0 1; R1 a b 10T; R2 b 0 10T
+        let mut system = LinearSystem::new(3);
+        system.stamp_conductance(Some(0), Some(1), 1e-13);
+        system.stamp_conductance(Some(1), None, 1e-13);
+        system.stamp_voltage(Some(0), None, 2, 1.0);
+        let x = system.solve().unwrap();
+        assert!(close(x[0], 1.0));
+        assert!(close(x[1], 0.5));
+        assert!(close(x[2], -5e-14));
+    }
+
+    #[test]
+    fn solves_teraohm_netlists() {
+        for (netlist, net, voltage) in [
+            ("t\nI1 0 a 1p\nR1 a 0 2T\n", "a", 2.0),
+            ("t\nV1 a 0 1\nR1 a b 10T\nR2 b 0 10T\n", "b", 0.5),
+        ] {
+            let mut circuit = crate::spice::parse_spice(netlist).unwrap();
+            circuit.solve_dc().unwrap();
+            assert!(close(circuit.net_voltage(net).unwrap(), voltage));
+        }
+    }
+
+    #[test]
+    fn reports_the_column_of_a_singular_system() {
+        // a node tied to nothing but a second node with no path to ground
+        let mut system = LinearSystem::new(2);
+        system.stamp_conductance(Some(0), Some(1), 1e-3);
+        system.stamp_current(None, Some(0), 1e-3);
+        assert_eq!(system.solve(), Err(1));
+
+        // a conductance that cancels out is still singular, however small the matrix
+        let mut system = LinearSystem::<f64>::new(1);
+        system.stamp_conductance(Some(0), None, 1e-15);
+        system.stamp_conductance(Some(0), None, -1e-15);
+        assert_eq!(system.solve(), Err(0));
+    }
+}
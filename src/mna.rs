@@ -0,0 +1,334 @@
+// src/mna.rs
+
+use std::collections::HashMap;
+
+use crate::complex::Complex;
+use crate::error::CircuitError;
+use crate::matrix::{LinearSystem, Scalar};
+use crate::nets::UnionFind;
+use crate::stamp::{Analysis, Stamper, Values};
+use crate::types::Circuit;
+
+// Newton-Raphson iteration limit and the tolerances two iterations must agree to
+const MAX_ITERATIONS: usize = 200;
+const ABS_TOLERANCE: f64 = 1e-9;
+const REL_TOLERANCE: f64 = 1e-6;
+
+// The unknowns belonging to a single component
+pub(crate) struct Entry {
+    pub nodes: Vec<Option<usize>>,
+    pub branches: Vec<usize>,
+    // when the component's current controls another component but it has no branch of its
+    // own, node1 is moved to an internal node and a 0 V source between the two measures it
+    pub sense: Option<(Option<usize>, usize)>,
+}
+
+// Layout of the unknowns in a Modified Nodal Analysis system.
+// The first unknowns are the voltages of every non-reference net, followed by the
+// branch currents each component asks for and any internal nodes and branches needed
+// to measure controlling currents.
+pub(crate) struct Layout {
+    pub size: usize,
+    pub names: Vec<String>,
+    pub node_index: Vec<Option<usize>>,
+    pub entries: HashMap<String, Entry>,
+}
+impl Layout {
+    // the unknown holding the current through a component from its node1 to its node2
+    pub fn current_unknown(&self, name: &str) -> Option<usize> {
+        let entry = self.entries.get(name)?;
+        entry.branches.first().copied().or(entry.sense.map(|(_, branch)| branch))
+    }
+
+    // solve a system built on this layout, reporting a floating node when a node voltage can't be found
+    pub fn solve<T: Scalar>(&self, system: &LinearSystem<T>) -> Result<Vec<T>, CircuitError> {
+        system.solve().map_err(|column| {
+            match self.node_index.iter().position(|index| *index == Some(column)) {
+                Some(node) => CircuitError::FloatingNode(node),
+                None => CircuitError::SingularMatrix,
+            }
+        })
+    }
+
+    pub fn node_values<T: Scalar>(&self, solution: &[T]) -> Vec<T> {
+        self.node_index
+            .iter()
+            .map(|index| match index {
+                Some(index) => solution[*index],
+                None => T::from(0.0),
+            })
+            .collect()
+    }
+
+    pub fn values<'a, T: Scalar>(&'a self, name: &str, solution: &'a [T], state: &'a [f64], point: &'a [f64]) -> Values<'a, T> {
+        Values::new(solution, self, &self.entries[name], state, point)
+    }
+}
+
+impl Circuit {
+    pub(crate) fn mna_layout(&self, analysis: &Analysis) -> Result<Layout, CircuitError> {
+        // nodes joined by wires form a single net with a single voltage
+        let net_map = self.net_map();
+        let net_count = net_map.iter().map(|net| net + 1).max().unwrap_or(0);
+
+        // every connected group of nets needs a reference, otherwise the system is singular
+        let references = self.reference_nets(&net_map, net_count)?;
+        let connected = self.connected_nets(&net_map);
+
+        // assign an unknown to every net that is not a reference and has something connected
+        let mut net_index: Vec<Option<usize>> = vec![None; net_count];
+        let mut size = 0;
+        for (net, index) in net_index.iter_mut().enumerate() {
+            if connected[net] && !references.contains(&net) {
+                *index = Some(size);
+                size += 1;
+            }
+        }
+        let node_index: Vec<Option<usize>> = net_map.iter().map(|&net| net_index[net]).collect();
+
+        // components whose current controls another component
+        let mut controls: Vec<&str> = Vec::new();
+        for component in self.components.values() {
+            if let Some(control) = component.controlling_current() {
+                if !self.components.contains_key(control) {
+                    return Err(CircuitError::UnknownComponent(control.to_string()));
+                }
+                controls.push(control);
+            }
+        }
+
+        let mut names: Vec<String> = self.components.keys().cloned().collect();
+        names.sort();
+
+        let mut entries = HashMap::new();
+        for name in &names {
+            let component = self.components[name].as_ref();
+            let mut nodes: Vec<Option<usize>> = component
+                .component()
+                .nodes
+                .iter()
+                .map(|&node| node_index[node])
+                .collect();
+
+            let branches: Vec<usize> = (0..component.branch_count(analysis)).map(|i| size + i).collect();
+            size += branches.len();
+
+            let mut sense = None;
+            if branches.is_empty() && controls.contains(&name.as_str()) {
+                if nodes.len() < 2 {
+                    return Err(CircuitError::InvalidValue {
+                        component: name.clone(),
+                        reason: "a component needs two terminals to control a dependent source".to_string(),
+                    });
+                }
+                sense = Some((nodes[0], size + 1));
+                nodes[0] = Some(size);
+                size += 2;
+            }
+
+            entries.insert(name.clone(), Entry { nodes, branches, sense });
+        }
+
+        Ok(Layout {
+            size,
+            names,
+            node_index,
+            entries,
+        })
+    }
+
+    // Solve the real valued system of a DC or transient analysis. `states` holds the transient
+    // state every component reported at the previous time point. Circuits with nonlinear
+    // components are solved by Newton-Raphson iteration, and `points` holds the point each one
+    // is linearized about. It is left at the converged points, which makes a good starting
+    // guess for the next solve.
+    pub(crate) fn solve_real(
+        &self,
+        layout: &Layout,
+        analysis: &Analysis,
+        states: &HashMap<String, Vec<f64>>,
+        points: &mut HashMap<String, Vec<f64>>,
+    ) -> Result<Vec<f64>, CircuitError> {
+        let nonlinear: Vec<&String> = layout.names.iter().filter(|name| self.components[*name].is_nonlinear()).collect();
+
+        let mut previous: Option<Vec<f64>> = None;
+        for _ in 0..MAX_ITERATIONS {
+            let system = self.assemble(layout, analysis, states, points)?;
+            let solution = layout.solve(&system)?;
+            if nonlinear.is_empty() {
+                return Ok(solution);
+            }
+
+            // move every nonlinear component to its next point, which may be limited short of the solution
+            let mut settled = true;
+            for name in &nonlinear {
+                let state = states.get(*name).map(|state| state.as_slice()).unwrap_or(&[]);
+                let point = points.get(*name).cloned().unwrap_or_default();
+                let next = self.components[*name].linearize(&layout.values(name, &solution, state, &point));
+                settled &= converged(&point, &next);
+                points.insert((*name).clone(), next);
+            }
+
+            if settled && previous.is_some_and(|previous| converged(&previous, &solution)) {
+                return Ok(solution);
+            }
+            previous = Some(solution);
+        }
+
+        Err(CircuitError::NoConvergence(MAX_ITERATIONS))
+    }
+
+    pub(crate) fn assemble(
+        &self,
+        layout: &Layout,
+        analysis: &Analysis,
+        states: &HashMap<String, Vec<f64>>,
+        points: &HashMap<String, Vec<f64>>,
+    ) -> Result<LinearSystem<f64>, CircuitError> {
+        let mut system = LinearSystem::new(layout.size);
+        for name in &layout.names {
+            let entry = &layout.entries[name];
+            let state = states.get(name).map(|state| state.as_slice()).unwrap_or(&[]);
+            let point = points.get(name).map(|point| point.as_slice()).unwrap_or(&[]);
+            let mut stamper = Stamper::new(&mut system, layout, entry, state, point);
+            self.components[name].stamp(&mut stamper, analysis)?;
+            stamp_sense(&mut system, entry);
+        }
+        Ok(system)
+    }
+
+    // the DC operating point every nonlinear component is linearized about for small signal analysis
+    pub(crate) fn small_signal_points(&self) -> Result<HashMap<String, Vec<f64>>, CircuitError> {
+        let mut points = HashMap::new();
+        if self.components.values().any(|component| component.is_nonlinear()) {
+            let layout = self.mna_layout(&Analysis::Dc)?;
+            self.solve_real(&layout, &Analysis::Dc, &HashMap::new(), &mut points)?;
+        }
+        Ok(points)
+    }
+
+    // `points` holds the DC operating point of every nonlinear component
+    pub(crate) fn assemble_ac(&self, layout: &Layout, omega: f64, points: &HashMap<String, Vec<f64>>) -> Result<LinearSystem<Complex>, CircuitError> {
+        let mut system = LinearSystem::new(layout.size);
+        for name in &layout.names {
+            let entry = &layout.entries[name];
+            let point = points.get(name).map(|point| point.as_slice()).unwrap_or(&[]);
+            let mut stamper = Stamper::new(&mut system, layout, entry, &[], point);
+            self.components[name].stamp_ac(&mut stamper, omega)?;
+            stamp_sense(&mut system, entry);
+        }
+        Ok(system)
+    }
+
+    // find the ground net of every group of nets joined by components, each group needs exactly one
+    fn reference_nets(&self, net_map: &[usize], net_count: usize) -> Result<Vec<usize>, CircuitError> {
+        let mut sets = UnionFind::new(net_count);
+        for component in self.components.values() {
+            let nodes = &component.component().nodes;
+            for pair in nodes.windows(2) {
+                sets.union(net_map[pair[0]], net_map[pair[1]]);
+            }
+        }
+
+        // the ground node found for each group, indexed by the group's root net
+        let mut grounds: Vec<Option<usize>> = vec![None; net_count];
+        for node in self.nodes.iter().filter(|node| node.ground) {
+            let group = sets.find(net_map[node.id]);
+            match grounds[group] {
+                Some(other) if net_map[other] != net_map[node.id] => {
+                    return Err(CircuitError::MultipleGrounds(other, node.id));
+                }
+                Some(_) => (),
+                None => grounds[group] = Some(node.id),
+            }
+        }
+
+        // nodes with nothing connected need no reference
+        let mut references = Vec::new();
+        for (node, &net) in net_map.iter().enumerate() {
+            if self.nodes[node].connected.is_empty() {
+                continue;
+            }
+            match grounds[sets.find(net)] {
+                Some(ground) => {
+                    if !references.contains(&net_map[ground]) {
+                        references.push(net_map[ground]);
+                    }
+                }
+                None => return Err(CircuitError::MissingGround(node)),
+            }
+        }
+        Ok(references)
+    }
+}
+
+fn stamp_sense<T: Scalar>(system: &mut LinearSystem<T>, entry: &Entry) {
+    if let Some((node1, branch)) = entry.sense {
+        system.stamp_voltage(node1, entry.nodes[0], branch, T::from(0.0));
+    }
+}
+
+fn converged(previous: &[f64], next: &[f64]) -> bool {
+    previous.len() == next.len()
+        && previous
+            .iter()
+            .zip(next)
+            .all(|(a, b)| (a - b).abs() <= ABS_TOLERANCE + REL_TOLERANCE * a.abs().max(b.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::CircuitError;
+    use crate::types::{Circuit, Polarity, Resistor, VoltageSource};
+
+    // V1 driving R1, with the nets named after `prefix`
+    fn source_loop(circuit: &mut Circuit, prefix: &str) {
+        let (source, resistor) = (format!("V{}", prefix), format!("R{}", prefix));
+        circuit.add_component(VoltageSource::new(&source, 2.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new(&resistor, 1.0)).unwrap();
+        let (plus, minus) = (format!("{}+", prefix), format!("{}-", prefix));
+        for (component, terminal, net) in [(&source, 1, &plus), (&source, 2, &minus), (&resistor, 1, &plus), (&resistor, 2, &minus)] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+    }
+
+    #[test]
+    fn voltages_are_relative_to_the_ground_of_each_subnetwork() {
+        let mut circuit = Circuit::new();
+        source_loop(&mut circuit, "a");
+        source_loop(&mut circuit, "b");
+        circuit.set_ground(circuit.net_node("a-").unwrap()).unwrap();
+        circuit.set_ground(circuit.net_node("b+").unwrap()).unwrap();
+        circuit.solve_dc().unwrap();
+
+        assert_eq!(circuit.net_voltage("a+"), Some(2.0));
+        assert_eq!(circuit.net_voltage("a-"), Some(0.0));
+        assert_eq!(circuit.net_voltage("b+"), Some(0.0));
+        assert_eq!(circuit.net_voltage("b-"), Some(-2.0));
+    }
+
+    #[test]
+    fn every_subnetwork_needs_exactly_one_ground() {
+        let mut circuit = Circuit::new();
+        source_loop(&mut circuit, "a");
+        source_loop(&mut circuit, "b");
+        circuit.set_ground(circuit.net_node("a-").unwrap()).unwrap();
+        let error = circuit.solve_dc().unwrap_err();
+        assert!(matches!(error, CircuitError::MissingGround(node) if circuit.net_name(node).unwrap().starts_with('b')));
+
+        // two grounds on different nets of the same subnetwork
+        let (plus, minus) = (circuit.net_node("a+").unwrap(), circuit.net_node("a-").unwrap());
+        circuit.set_ground(plus).unwrap();
+        assert_eq!(circuit.solve_dc().unwrap_err(), CircuitError::MultipleGrounds(plus, minus));
+
+        // grounds on the same net are one reference
+        let mut circuit = Circuit::new();
+        source_loop(&mut circuit, "a");
+        let (source_minus, resistor_minus) = (circuit.net_node("a-").unwrap(), circuit.get_component("Ra").unwrap().component().nodes[1]);
+        assert_ne!(source_minus, resistor_minus);
+        circuit.set_ground(source_minus).unwrap();
+        circuit.set_ground(resistor_minus).unwrap();
+        circuit.solve_dc().unwrap();
+        assert_eq!(circuit.net_voltage("a+"), Some(2.0));
+    }
+}
@@ -0,0 +1,329 @@
+// src/nets.rs
+
+use crate::error::CircuitError;
+use crate::types::Circuit;
+
+// Electrical nets
+// A net is a group of nodes that are joined together by wires and therefore
+// share a single voltage.
+pub struct Net {
+    pub id: usize,
+    pub nodes: Vec<usize>,
+    pub voltage: Option<f64>,
+    pub name: Option<String>,
+}
+
+impl Circuit {
+    pub fn nets(&self) -> Vec<Net> {
+        let net_map = self.net_map();
+        let count = net_map.iter().map(|net| net + 1).max().unwrap_or(0);
+
+        let mut nets: Vec<Net> = (0..count)
+            .map(|id| Net {
+                id,
+                nodes: Vec::new(),
+                voltage: None,
+                name: None,
+            })
+            .collect();
+        for (node, &net) in net_map.iter().enumerate() {
+            nets[net].nodes.push(node);
+        }
+        for net in nets.iter_mut() {
+            net.voltage = self.nodes[net.nodes[0]].voltage;
+            net.name = net.nodes.iter().find_map(|&node| self.nodes[node].label.clone());
+        }
+
+        nets
+    }
+
+    // map every node id to the id of the net it belongs to
+    // nets are numbered in order of their lowest node id
+    pub fn net_map(&self) -> Vec<usize> {
+        let mut sets = UnionFind::new(self.nodes.len());
+        for wire in self.wires.values() {
+            sets.union(wire.node1, wire.node2);
+        }
+        sets.labels()
+    }
+
+    pub fn net_of(&self, node: usize) -> Option<usize> {
+        self.net_map().get(node).copied()
+    }
+
+    // whether anything is connected to each net, which is not the case for the nodes a removed
+    // component leaves behind until the circuit is compacted
+    pub(crate) fn connected_nets(&self, net_map: &[usize]) -> Vec<bool> {
+        let net_count = net_map.iter().map(|net| net + 1).max().unwrap_or(0);
+        let mut connected = vec![false; net_count];
+        for node in self.nodes.iter().filter(|node| !node.connected.is_empty()) {
+            connected[net_map[node.id]] = true;
+        }
+        connected
+    }
+
+    // Named nets
+    // A net is named by the label of its nodes. Attaching a terminal to a named net wires it
+    // to the net, creating the net from the terminal's node if there is no net of that name
+    // yet. The nets "0" and "gnd" are ground.
+
+    // attach a component's terminal, numbered from 1 as in node1 and node2, to a named net
+    pub fn attach(&mut self, component: &str, terminal: usize, net: &str) -> Result<(), CircuitError> {
+        let node = self.find_terminal(component, terminal.checked_sub(1), &terminal.to_string())?;
+        self.attach_node(node, net)
+    }
+
+    // attach a terminal by name, e.g. circuit.attach_terminal("Q1", "collector", "vout")
+    pub fn attach_terminal(&mut self, component: &str, terminal: &str, net: &str) -> Result<(), CircuitError> {
+        let index = self
+            .get_component(component)
+            .and_then(|component| component.terminals().iter().position(|name| *name == terminal));
+        let node = self.find_terminal(component, index, terminal)?;
+        self.attach_node(node, net)
+    }
+
+    pub fn attach_node(&mut self, node: usize, net: &str) -> Result<(), CircuitError> {
+        if self.get_node(node).is_none() {
+            return Err(CircuitError::UnknownNode(node));
+        }
+        let net_map = self.net_map();
+        if let Some(current) = self.net_name(node) {
+            if current == net || (is_ground_name(&current) && is_ground_name(net)) {
+                return Ok(());
+            }
+            return Err(CircuitError::AlreadyAttached(node, current));
+        }
+
+        match self.net_node(net) {
+            Some(existing) if net_map[existing] != net_map[node] => {
+                self.connect(existing, node)?;
+            }
+            Some(_) => (),
+            None => {
+                self.nodes[node].label = Some(net.to_string());
+                if is_ground_name(net) {
+                    self.set_ground(node)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // a node on the net with this name, where "0" and "gnd" name the same net
+    pub fn net_node(&self, name: &str) -> Option<usize> {
+        self.nodes
+            .iter()
+            .find(|node| match &node.label {
+                Some(label) => label == name || (is_ground_name(label) && is_ground_name(name)),
+                None => false,
+            })
+            .map(|node| node.id)
+    }
+
+    // the name of the net a node is on
+    pub fn net_name(&self, node: usize) -> Option<String> {
+        let net_map = self.net_map();
+        let net = *net_map.get(node)?;
+        (0..net_map.len())
+            .filter(|&other| net_map[other] == net)
+            .find_map(|other| self.nodes[other].label.clone())
+    }
+
+    pub fn net_by_name(&self, name: &str) -> Option<Net> {
+        let net = self.net_of(self.net_node(name)?)?;
+        self.nets().into_iter().nth(net)
+    }
+
+    // the voltage of a named net after a DC solve
+    pub fn net_voltage(&self, name: &str) -> Option<f64> {
+        self.nodes[self.net_node(name)?].voltage
+    }
+
+    fn find_terminal(&self, component: &str, index: Option<usize>, terminal: &str) -> Result<usize, CircuitError> {
+        let component = self
+            .get_component(component)
+            .ok_or_else(|| CircuitError::UnknownComponent(component.to_string()))?;
+        index
+            .and_then(|index| component.component().node(index))
+            .ok_or_else(|| CircuitError::UnknownTerminal {
+                component: component.component().name.clone(),
+                terminal: terminal.to_string(),
+            })
+    }
+}
+
+fn is_ground_name(name: &str) -> bool {
+    name == "0" || name.eq_ignore_ascii_case("gnd")
+}
+
+// Disjoint set over 0..size, where the root of every set is its smallest member
+pub(crate) struct UnionFind {
+    parent: Vec<usize>,
+}
+impl UnionFind {
+    pub fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    pub fn find(&mut self, item: usize) -> usize {
+        let mut root = item;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+
+        // compress the path to the root
+        let mut current = item;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+
+        root
+    }
+
+    pub fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+
+    // number the sets 0.. in order of their smallest member and label every item with its set
+    pub fn labels(&mut self) -> Vec<usize> {
+        let size = self.parent.len();
+        let mut label: Vec<Option<usize>> = vec![None; size];
+        let mut labels = Vec::with_capacity(size);
+        let mut count = 0;
+        for item in 0..size {
+            let root = self.find(item);
+            if label[root].is_none() {
+                label[root] = Some(count);
+                count += 1;
+            }
+            labels.push(label[root].unwrap());
+        }
+        labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Polarity, Resistor, VoltageSource};
+
+    #[test]
+    fn union_find_numbers_sets_by_their_smallest_member() {
+        let mut sets = UnionFind::new(6);
+        sets.union(4, 1);
+        sets.union(5, 3);
+        sets.union(3, 4);
+        assert_eq!(sets.find(5), 1);
+        assert_eq!(sets.labels(), [0, 1, 2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn wires_merge_nodes_into_nets() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        circuit.add_component(Resistor::new("R2", 1.0)).unwrap();
+        // R1 has nodes 0 and 1, R2 has nodes 2 and 3
+        circuit.connect(1, 2).unwrap();
+        let wire = circuit.connect(3, 0).unwrap();
+        assert_eq!(circuit.net_map(), [0, 1, 1, 0]);
+
+        let nets = circuit.nets();
+        assert_eq!(nets.len(), 2);
+        assert_eq!((nets[0].id, nets[0].nodes.clone()), (0, vec![0, 3]));
+        assert_eq!((nets[1].id, nets[1].nodes.clone()), (1, vec![1, 2]));
+
+        // removing a wire splits its net again
+        circuit.disconnect(wire.id).unwrap();
+        assert_eq!(circuit.net_map(), [0, 1, 1, 2]);
+        assert_eq!(circuit.net_of(3), Some(2));
+        assert_eq!(circuit.net_of(4), None);
+    }
+
+    #[test]
+    fn nets_report_the_voltage_and_label_of_their_nodes() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 3.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        circuit.connect(0, 2).unwrap();
+        circuit.connect(1, 3).unwrap();
+        circuit.get_node_mut(2).unwrap().label = Some("top".to_string());
+        circuit.set_ground(1).unwrap();
+        circuit.solve_dc().unwrap();
+
+        let nets = circuit.nets();
+        assert_eq!(nets[0].nodes, [0, 2]);
+        assert_eq!((nets[0].name.as_deref(), nets[0].voltage), (Some("top"), Some(3.0)));
+        assert_eq!((nets[1].name.as_deref(), nets[1].voltage), (None, Some(0.0)));
+    }
+
+    #[test]
+    fn attaching_terminals_to_a_net_joins_their_nodes() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 2.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        // V1 has nodes 0 and 1, R1 has nodes 2 and 3
+        circuit.attach("V1", 1, "top").unwrap();
+        circuit.attach("R1", 1, "top").unwrap();
+        circuit.attach_terminal("V1", "node2", "0").unwrap();
+        circuit.attach_terminal("R1", "node2", "gnd").unwrap();
+        assert_eq!(circuit.net_map(), [0, 1, 0, 1]);
+        assert!(circuit.nodes[1].ground);
+        assert_eq!(circuit.net_name(2).as_deref(), Some("top"));
+        assert_eq!(circuit.net_node("GND"), Some(1));
+
+        // attaching again to the same net does nothing, and a node can't move to another one
+        circuit.attach("R1", 1, "top").unwrap();
+        circuit.attach("R1", 2, "0").unwrap();
+        assert_eq!(circuit.attach("R1", 1, "out"), Err(CircuitError::AlreadyAttached(2, "top".to_string())));
+        assert_eq!(circuit.net_map(), [0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn attach_rejects_unknown_components_and_terminals() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        let unknown_terminal = |terminal: &str| CircuitError::UnknownTerminal {
+            component: "R1".to_string(),
+            terminal: terminal.to_string(),
+        };
+        // terminals are numbered from 1
+        assert_eq!(circuit.attach("R1", 0, "a"), Err(unknown_terminal("0")));
+        assert_eq!(circuit.attach("R1", 3, "a"), Err(unknown_terminal("3")));
+        assert_eq!(circuit.attach_terminal("R1", "gate", "a"), Err(unknown_terminal("gate")));
+        assert_eq!(circuit.attach("R2", 1, "a"), Err(CircuitError::UnknownComponent("R2".to_string())));
+        assert_eq!(circuit.attach_node(5, "a"), Err(CircuitError::UnknownNode(5)));
+        assert_eq!(circuit.net_node("a"), None);
+    }
+
+    #[test]
+    fn named_nets_can_be_looked_up_and_read_after_a_solve() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 4.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        circuit.add_component(Resistor::new("R2", 3.0)).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "gnd"), ("R1", 1, "in"), ("R1", 2, "out"), ("R2", 1, "out"), ("R2", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+
+        let out = circuit.net_by_name("out").unwrap();
+        assert_eq!((out.nodes, out.name.as_deref(), out.voltage), (vec![3, 4], Some("out"), None));
+        // ground is found under either of its names
+        assert_eq!(circuit.net_by_name("0").unwrap().nodes, [1, 5]);
+        assert!(circuit.net_by_name("missing").is_none());
+        assert_eq!(circuit.net_voltage("out"), None);
+
+        circuit.solve_dc().unwrap();
+        assert_eq!(circuit.net_voltage("in"), Some(4.0));
+        assert!((circuit.net_voltage("out").unwrap() - 3.0).abs() < 1e-12);
+        assert_eq!(circuit.net_voltage("gnd"), Some(0.0));
+        assert_eq!(circuit.net_by_name("out").unwrap().voltage, circuit.net_voltage("out"));
+        assert_eq!(circuit.net_voltage("missing"), None);
+    }
+}
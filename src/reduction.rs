@@ -0,0 +1,465 @@
+// src/reduction.rs
+
+use std::fmt;
+
+use crate::error::CircuitError;
+use crate::nets::UnionFind;
+use crate::types::{Capacitor, Circuit, CurrentSource, Inductor, Resistor, VoltageSource};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReductionKind {
+    Series,
+    Parallel,
+    // a net joined to three others is replaced by a triangle between them
+    WyeDelta,
+    // the general form of the wye-delta transform for a net joined to four or more others
+    StarMesh,
+    // both ends are on the same net
+    ShortedOut,
+    // no current can flow through it
+    Open,
+    // an independent source is switched off, shorting voltage sources and opening current sources
+    SourceOff,
+}
+
+// One step of a reduction: the elements it removed and the elements that replace them
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReductionStep {
+    pub kind: ReductionKind,
+    pub removed: Vec<String>,
+    pub added: Vec<(String, f64)>,
+}
+impl fmt::Display for ReductionStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let removed = self.removed.join(", ");
+        match self.kind {
+            ReductionKind::ShortedOut => return write!(f, "{} is shorted out", removed),
+            ReductionKind::Open => return write!(f, "{} carries no current", removed),
+            ReductionKind::SourceOff => return write!(f, "{} is switched off", removed),
+            ReductionKind::Series => write!(f, "{} in series", removed)?,
+            ReductionKind::Parallel => write!(f, "{} in parallel", removed)?,
+            ReductionKind::WyeDelta => write!(f, "{} wye to delta", removed)?,
+            ReductionKind::StarMesh => write!(f, "{} star to mesh", removed)?,
+        }
+        let added: Vec<String> = self.added.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+        write!(f, ": {}", added.join(", "))
+    }
+}
+
+// Result of reducing a network to a single element between two nodes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reduction {
+    pub value: f64,
+    pub steps: Vec<ReductionStep>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Element {
+    Resistor,
+    Capacitor,
+    Inductor,
+}
+impl Element {
+    // Elements are combined as impedance-like values, which add in series: resistance,
+    // inductance, and the reciprocal of capacitance
+    fn impedance(self, value: f64) -> f64 {
+        match self {
+            Element::Capacitor => 1.0 / value,
+            _ => value,
+        }
+    }
+
+    fn value(self, impedance: f64) -> f64 {
+        self.impedance(impedance)
+    }
+
+    // prefix of the names given to the elements a reduction creates
+    fn prefix(self) -> &'static str {
+        match self {
+            Element::Resistor => "Req",
+            Element::Capacitor => "Ceq",
+            Element::Inductor => "Leq",
+        }
+    }
+}
+
+struct Edge {
+    name: String,
+    nets: (usize, usize),
+    impedance: f64,
+}
+
+// Series/parallel reduction.
+// Only components of the kind being reduced and independent sources, which are switched off,
+// may be in the circuit. When no series or parallel combination is left, a net joined to three
+// or more others is removed with a wye-delta (star-mesh) transform.
+impl Circuit {
+    pub fn equivalent_resistance(&self, node_a: usize, node_b: usize) -> Result<Reduction, CircuitError> {
+        self.reduce(node_a, node_b, Element::Resistor)
+    }
+
+    pub fn equivalent_capacitance(&self, node_a: usize, node_b: usize) -> Result<Reduction, CircuitError> {
+        self.reduce(node_a, node_b, Element::Capacitor)
+    }
+
+    pub fn equivalent_inductance(&self, node_a: usize, node_b: usize) -> Result<Reduction, CircuitError> {
+        self.reduce(node_a, node_b, Element::Inductor)
+    }
+
+    fn reduce(&self, node_a: usize, node_b: usize, element: Element) -> Result<Reduction, CircuitError> {
+        for node in [node_a, node_b] {
+            if self.get_node(node).is_none() {
+                return Err(CircuitError::UnknownNode(node));
+            }
+        }
+
+        let mut steps = Vec::new();
+        let net_map = self.net_map();
+        let net_count = net_map.iter().map(|net| net + 1).max().unwrap_or(0);
+        let mut sets = UnionFind::new(net_count);
+
+        let mut names: Vec<&String> = self.components.keys().collect();
+        names.sort();
+        let mut edges = Vec::new();
+        for name in names {
+            let component = self.components[name].as_ref();
+            let base = component.component();
+            let nets = (net_map[base.nodes[0]], net_map[base.nodes[1]]);
+            let any = component.as_any();
+
+            let value = match element {
+                Element::Resistor => any.downcast_ref::<Resistor>().map(|resistor| resistor.resistance),
+                Element::Capacitor => any.downcast_ref::<Capacitor>().map(|capacitor| capacitor.capacitance),
+                Element::Inductor => any.downcast_ref::<Inductor>().map(|inductor| inductor.inductance),
+            };
+            match value {
+                Some(value) if value > 0.0 && value.is_finite() => edges.push(Edge {
+                    name: name.clone(),
+                    nets,
+                    impedance: element.impedance(value),
+                }),
+                Some(_) => {
+                    return Err(CircuitError::InvalidValue {
+                        component: name.clone(),
+                        reason: "value must be positive".to_string(),
+                    })
+                }
+                None if any.is::<VoltageSource>() || any.is::<CurrentSource>() => {
+                    if any.is::<VoltageSource>() {
+                        sets.union(nets.0, nets.1);
+                    }
+                    steps.push(ReductionStep {
+                        kind: ReductionKind::SourceOff,
+                        removed: vec![name.clone()],
+                        added: Vec::new(),
+                    });
+                }
+                None => {
+                    return Err(CircuitError::InvalidAnalysis(format!("{} can't be part of a series/parallel reduction", name)));
+                }
+            }
+        }
+
+        // switched off voltage sources join the nets at their ends
+        let nets = sets.labels();
+        for edge in edges.iter_mut() {
+            edge.nets = (nets[edge.nets.0], nets[edge.nets.1]);
+        }
+        let (a, b) = (nets[net_map[node_a]], nets[net_map[node_b]]);
+        if a == b {
+            return Err(CircuitError::InvalidAnalysis(format!("nodes {} and {} are shorted together", node_a, node_b)));
+        }
+
+        let mut reducer = Reducer {
+            edges,
+            steps,
+            terminals: (a, b),
+            prefix: element.prefix(),
+            created: 0,
+        };
+        let impedance = reducer.run()?.ok_or_else(|| {
+            CircuitError::InvalidAnalysis(format!("nodes {} and {} are not connected", node_a, node_b))
+        })?;
+
+        // report every value in the units of the element being reduced
+        let mut steps = reducer.steps;
+        for step in steps.iter_mut() {
+            for (_, value) in step.added.iter_mut() {
+                *value = element.value(*value);
+            }
+        }
+
+        Ok(Reduction {
+            value: element.value(impedance),
+            steps,
+        })
+    }
+}
+
+struct Reducer {
+    edges: Vec<Edge>,
+    steps: Vec<ReductionStep>,
+    terminals: (usize, usize),
+    prefix: &'static str,
+    created: usize,
+}
+impl Reducer {
+    // reduce until a single edge joins the terminals, returning its impedance or None if they
+    // aren't connected
+    fn run(&mut self) -> Result<Option<f64>, CircuitError> {
+        loop {
+            if self.remove_shorted() || self.remove_open() || self.combine_parallel() || self.combine_series() {
+                continue;
+            }
+            if let [edge] = self.edges.as_slice() {
+                return Ok(Some(edge.impedance));
+            }
+            if self.edges.is_empty() {
+                return Ok(None);
+            }
+            if !self.star_mesh() {
+                return Err(CircuitError::InvalidAnalysis("the network could not be reduced".to_string()));
+            }
+        }
+    }
+
+    fn is_terminal(&self, net: usize) -> bool {
+        net == self.terminals.0 || net == self.terminals.1
+    }
+
+    // indices of the edges touching a net
+    fn edges_at(&self, net: usize) -> Vec<usize> {
+        (0..self.edges.len())
+            .filter(|&i| self.edges[i].nets.0 == net || self.edges[i].nets.1 == net)
+            .collect()
+    }
+
+    // the nets that aren't terminals, in order
+    fn internal_nets(&self) -> Vec<usize> {
+        let mut nets: Vec<usize> = self
+            .edges
+            .iter()
+            .flat_map(|edge| [edge.nets.0, edge.nets.1])
+            .filter(|&net| !self.is_terminal(net))
+            .collect();
+        nets.sort();
+        nets.dedup();
+        nets
+    }
+
+    // name for a newly created element
+    fn next_name(&mut self) -> String {
+        self.created += 1;
+        format!("{}{}", self.prefix, self.created)
+    }
+
+    fn remove(&mut self, mut indices: Vec<usize>) -> Vec<Edge> {
+        indices.sort();
+        indices.into_iter().rev().map(|i| self.edges.remove(i)).collect::<Vec<_>>().into_iter().rev().collect()
+    }
+
+    fn record(&mut self, kind: ReductionKind, removed: &[Edge], added: Vec<Edge>) {
+        self.steps.push(ReductionStep {
+            kind,
+            removed: removed.iter().map(|edge| edge.name.clone()).collect(),
+            added: added.iter().map(|edge| (edge.name.clone(), edge.impedance)).collect(),
+        });
+        self.edges.extend(added);
+    }
+
+    fn remove_shorted(&mut self) -> bool {
+        let Some(index) = self.edges.iter().position(|edge| edge.nets.0 == edge.nets.1) else {
+            return false;
+        };
+        let removed = self.remove(vec![index]);
+        self.record(ReductionKind::ShortedOut, &removed, Vec::new());
+        true
+    }
+
+    // an edge whose far end leads nowhere
+    fn remove_open(&mut self) -> bool {
+        for net in self.internal_nets() {
+            let edges = self.edges_at(net);
+            if edges.len() == 1 {
+                let removed = self.remove(edges);
+                self.record(ReductionKind::Open, &removed, Vec::new());
+                return true;
+            }
+        }
+        false
+    }
+
+    fn combine_parallel(&mut self) -> bool {
+        for i in 0..self.edges.len() {
+            let (x, y) = self.edges[i].nets;
+            let parallel: Vec<usize> = (0..self.edges.len())
+                .filter(|&j| {
+                    let nets = self.edges[j].nets;
+                    nets == (x, y) || nets == (y, x)
+                })
+                .collect();
+            if parallel.len() < 2 {
+                continue;
+            }
+
+            let removed = self.remove(parallel);
+            let admittance: f64 = removed.iter().map(|edge| 1.0 / edge.impedance).sum();
+            let combined = Edge {
+                name: self.next_name(),
+                nets: (x, y),
+                impedance: 1.0 / admittance,
+            };
+            self.record(ReductionKind::Parallel, &removed, vec![combined]);
+            return true;
+        }
+        false
+    }
+
+    fn combine_series(&mut self) -> bool {
+        for net in self.internal_nets() {
+            let edges = self.edges_at(net);
+            if edges.len() != 2 {
+                continue;
+            }
+
+            let removed = self.remove(edges);
+            let far_end = |edge: &Edge| if edge.nets.0 == net { edge.nets.1 } else { edge.nets.0 };
+            let combined = Edge {
+                name: self.next_name(),
+                nets: (far_end(&removed[0]), far_end(&removed[1])),
+                impedance: removed[0].impedance + removed[1].impedance,
+            };
+            self.record(ReductionKind::Series, &removed, vec![combined]);
+            return true;
+        }
+        false
+    }
+
+    // remove the internal net with the fewest connections, joining each pair of its neighbours
+    // with an admittance of y_i * y_j / sum(y)
+    fn star_mesh(&mut self) -> bool {
+        let Some(net) = self.internal_nets().into_iter().min_by_key(|&net| self.edges_at(net).len()) else {
+            return false;
+        };
+
+        let removed = self.remove(self.edges_at(net));
+        let total: f64 = removed.iter().map(|edge| 1.0 / edge.impedance).sum();
+        let far_end = |edge: &Edge| if edge.nets.0 == net { edge.nets.1 } else { edge.nets.0 };
+
+        let mut added = Vec::new();
+        for (i, first) in removed.iter().enumerate() {
+            for second in &removed[i + 1..] {
+                added.push(Edge {
+                    name: self.next_name(),
+                    nets: (far_end(first), far_end(second)),
+                    impedance: first.impedance * second.impedance * total,
+                });
+            }
+        }
+
+        let kind = if removed.len() == 3 { ReductionKind::WyeDelta } else { ReductionKind::StarMesh };
+        self.record(kind, &removed, added);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spice::parse_spice;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9 * b.abs().max(1.0)
+    }
+
+    fn resistance(netlist: &str) -> Result<Reduction, CircuitError> {
+        let circuit = parse_spice(netlist).unwrap();
+        circuit.equivalent_resistance(circuit.net_node("a").unwrap(), circuit.net_node("b").unwrap())
+    }
+
+    fn kinds(reduction: &Reduction) -> Vec<ReductionKind> {
+        reduction.steps.iter().map(|step| step.kind).collect()
+    }
+
+    #[test]
+    fn combines_series_and_parallel_resistors() {
+        // R1 in series with R2 || R3
+        let reduction = resistance("t\nR1 a c 1k\nR2 c b 2k\nR3 c b 2k\n").unwrap();
+        assert!(close(reduction.value, 2e3));
+        assert_eq!(kinds(&reduction), vec![ReductionKind::Parallel, ReductionKind::Series]);
+        assert_eq!(reduction.steps[0].to_string(), "R2, R3 in parallel: Req1 = 1000");
+        assert_eq!(reduction.steps[1].to_string(), "R1, Req1 in series: Req2 = 2000");
+    }
+
+    #[test]
+    fn drops_shorted_and_open_elements() {
+        // R2 has both ends on c, and R3 leads to a net with nothing else on it
+        let reduction = resistance("t\nR1 a c 1k\nR2 c c 5k\nR3 c d 5k\nR4 c b 1k\n").unwrap();
+        assert!(close(reduction.value, 2e3));
+        assert_eq!(kinds(&reduction), vec![ReductionKind::ShortedOut, ReductionKind::Open, ReductionKind::Series]);
+        assert_eq!(reduction.steps[0].to_string(), "R2 is shorted out");
+        assert_eq!(reduction.steps[1].to_string(), "R3 carries no current");
+    }
+
+    #[test]
+    fn switches_off_independent_sources() {
+        // V1 shorts c to b, putting R2 in parallel with R3, and I1 is left open
+        let reduction = resistance("t\nV1 c b 5\nI1 a b 1m\nR1 a c 1k\nR2 a b 2k\nR3 a c 2k\n").unwrap();
+        assert!(close(reduction.value, 0.5e3));
+        assert_eq!(&kinds(&reduction)[..2], &[ReductionKind::SourceOff, ReductionKind::SourceOff]);
+        assert_eq!(reduction.steps[0].to_string(), "I1 is switched off");
+        assert_eq!(reduction.steps[1].to_string(), "V1 is switched off");
+    }
+
+    #[test]
+    fn reduces_a_bridge_with_a_wye_delta_transform() {
+        let reduction = resistance("t\nR1 a c 1\nR2 a d 2\nR3 c d 3\nR4 c b 4\nR5 d b 5\n").unwrap();
+        assert!(close(reduction.value, 61.0 / 21.0));
+        assert!((reduction.value - 2.9048).abs() < 1e-4);
+        assert_eq!(reduction.steps[0].kind, ReductionKind::WyeDelta);
+        assert_eq!(reduction.steps[0].removed, vec!["R1", "R3", "R4"]);
+        assert_eq!(reduction.steps[0].added.len(), 3);
+    }
+
+    #[test]
+    fn reduces_a_complete_graph_with_a_star_mesh_transform() {
+        // every pair of five nets joined by 1 ohm, which is 2/5 ohm between any two of them
+        let nets = ["a", "b", "c", "d", "e"];
+        let mut netlist = "t\n".to_string();
+        for (i, first) in nets.iter().enumerate() {
+            for second in &nets[i + 1..] {
+                netlist.push_str(&format!("R{}{} {} {} 1\n", first, second, first, second));
+            }
+        }
+        let reduction = resistance(&netlist).unwrap();
+        assert!(close(reduction.value, 0.4));
+        assert_eq!(reduction.steps[0].kind, ReductionKind::StarMesh);
+        assert_eq!(reduction.steps[0].removed.len(), 4);
+        assert_eq!(reduction.steps[0].added.len(), 6);
+    }
+
+    #[test]
+    fn capacitors_combine_as_reciprocals() {
+        // C1 in series with C2 || C3
+        let circuit = parse_spice("t\nC1 a c 3u\nC2 c b 1u\nC3 c b 2u\n").unwrap();
+        let (a, b) = (circuit.net_node("a").unwrap(), circuit.net_node("b").unwrap());
+        let reduction = circuit.equivalent_capacitance(a, b).unwrap();
+        assert!(close(reduction.value, 1.5e-6));
+        assert_eq!(reduction.steps[0].added[0].0, "Ceq1");
+        assert!(close(reduction.steps[0].added[0].1, 3e-6));
+
+        let circuit = parse_spice("t\nL1 a c 1m\nL2 c b 2m\n").unwrap();
+        let (a, b) = (circuit.net_node("a").unwrap(), circuit.net_node("b").unwrap());
+        assert!(close(circuit.equivalent_inductance(a, b).unwrap().value, 3e-3));
+    }
+
+    #[test]
+    fn rejects_terminals_without_a_finite_equivalent() {
+        let error = resistance("t\nR1 a c 1k\nR2 b d 1k\n").unwrap_err();
+        assert!(matches!(error, CircuitError::InvalidAnalysis(reason) if reason.ends_with("are not connected")));
+        let error = resistance("t\nV1 a b 1\nR1 a b 1k\n").unwrap_err();
+        assert!(matches!(error, CircuitError::InvalidAnalysis(reason) if reason.ends_with("are shorted together")));
+        let error = resistance("t\nR1 a b 1k\nD1 a b DX\n.model DX D\n").unwrap_err();
+        assert_eq!(error, CircuitError::InvalidAnalysis("D1 can't be part of a series/parallel reduction".to_string()));
+    }
+}
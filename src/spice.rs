@@ -0,0 +1,1210 @@
+// src/spice.rs
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::error::CircuitError;
+use crate::types::{
+    Bjt, BjtType, Capacitor, Cccs, Ccvs, Circuit, Component, CurrentSource, Diode, Inductor, Mosfet, MosfetType, OpAmp,
+    Polarity, Resistor, Vccs, Vcvs, VoltageSource,
+};
+use crate::waveform::Waveform;
+
+// Error produced while reading a SPICE netlist, pointing at the offending line and column (both starting at 1)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpiceError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+impl SpiceError {
+    fn new(token: &Token, message: impl Into<String>) -> Self {
+        Self {
+            line: token.line,
+            column: token.column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SpiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for SpiceError {}
+
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+// Parse a SPICE netlist into a circuit.
+//
+// As in SPICE, the first line is the title and is ignored. Element lines for R, C, L, V, I
+// and the dependent sources E, F, G and H are supported, with values using the usual
+// engineering suffixes (4.7k, 10u, 2meg). Independent sources accept the SIN, PULSE, PWL and
+// EXP functions. Comments start with '*' (whole line) or ';' and '+' continues the previous
+// line. Nets keep their names as node labels, and the net named "0" (or "gnd") is the
+// ground. Parsing stops at .end and other dot commands are ignored.
+//
+// Diodes (D), BJTs (Q) and level 1 MOSFETs (M) take their parameters from .model cards, which
+// may come before or after the elements using them. Model parameters the devices don't
+// implement, such as junction capacitances, are ignored.
+pub fn parse_spice(netlist: &str) -> Result<Circuit, SpiceError> {
+    let mut circuit = Circuit::new();
+    let mut nets: HashMap<String, usize> = HashMap::new();
+    let statements = statements(netlist);
+    let models = models(&statements)?;
+
+    for statement in &statements {
+        let first = &statement[0];
+        let keyword = first.text.to_lowercase();
+
+        if keyword == ".end" {
+            break;
+        }
+        if keyword.starts_with('.') {
+            continue;
+        }
+
+        if statement.len() < 4 {
+            return Err(SpiceError::new(first, format!("{} needs two nodes and a value", first.text)));
+        }
+        let name = &first.text;
+        let (node1, node2) = (&statement[1], &statement[2]);
+        let rest = &statement[3..];
+
+        match keyword.chars().next().unwrap() {
+            'r' => {
+                let resistance = value(&rest[0])?;
+                let resistor = Resistor::new(name, resistance);
+                expect_end(&rest[1..])?;
+                add(&mut circuit, &mut nets, resistor, first, &[node1, node2])?;
+            }
+            'c' => {
+                let mut capacitor = Capacitor::new(name, value(&rest[0])?);
+                capacitor.initial_voltage = initial_condition(&rest[1..])?;
+                add(&mut circuit, &mut nets, capacitor, first, &[node1, node2])?;
+            }
+            'l' => {
+                let mut inductor = Inductor::new(name, value(&rest[0])?);
+                inductor.initial_current = initial_condition(&rest[1..])?;
+                add(&mut circuit, &mut nets, inductor, first, &[node1, node2])?;
+            }
+            'v' => {
+                let source = source_values(rest)?;
+                let mut voltage_source = VoltageSource::new(name, source.dc, Polarity::Normal);
+                voltage_source.ac_magnitude = source.ac_magnitude;
+                voltage_source.ac_phase = source.ac_phase;
+                voltage_source.waveform = source.waveform;
+                add(&mut circuit, &mut nets, voltage_source, first, &[node1, node2])?;
+            }
+            'i' => {
+                // in SPICE the current flows from the first node through the source to the second
+                let source = source_values(rest)?;
+                let mut current_source = CurrentSource::new(name, source.dc, Polarity::Inverted);
+                current_source.ac_magnitude = source.ac_magnitude;
+                current_source.ac_phase = source.ac_phase;
+                current_source.waveform = source.waveform;
+                add(&mut circuit, &mut nets, current_source, first, &[node1, node2])?;
+            }
+            'e' | 'g' => {
+                if rest.len() < 3 {
+                    return Err(SpiceError::new(first, format!("{} needs two nodes, two controlling nodes and a value", first.text)));
+                }
+                let (control_positive, control_negative) = (&rest[0], &rest[1]);
+                let gain = value(&rest[2])?;
+                expect_end(&rest[3..])?;
+                if keyword.starts_with('e') {
+                    let terminals = [node1, node2, control_positive, control_negative];
+                    add(&mut circuit, &mut nets, Vcvs::new(name, gain), first, &terminals)?;
+                } else {
+                    // SPICE current flows through the source from the first node to the second
+                    let terminals = [node2, node1, control_positive, control_negative];
+                    add(&mut circuit, &mut nets, Vccs::new(name, gain), first, &terminals)?;
+                }
+            }
+            'f' | 'h' => {
+                // the controlling current is the current through a voltage source from + to -
+                if rest.len() < 2 {
+                    return Err(SpiceError::new(first, format!("{} needs a controlling source and a value", first.text)));
+                }
+                let (control, gain) = (&rest[0].text, value(&rest[1])?);
+                expect_end(&rest[2..])?;
+                if keyword.starts_with('f') {
+                    // SPICE current flows through the source from the first node to the second
+                    add(&mut circuit, &mut nets, Cccs::new(name, gain, control), first, &[node2, node1])?;
+                } else {
+                    add(&mut circuit, &mut nets, Ccvs::new(name, gain, control), first, &[node1, node2])?;
+                }
+            }
+            'd' => {
+                let model = find_model(&models, &rest[0], &["D"])?;
+                expect_end(&rest[1..])?;
+                let mut diode = Diode::new(name);
+                diode.saturation_current = model.parameter("is", diode.saturation_current);
+                diode.emission_coefficient = model.parameter("n", diode.emission_coefficient);
+                diode.series_resistance = model.parameter("rs", diode.series_resistance);
+                diode.breakdown_voltage = model.parameters.get("bv").copied();
+                add(&mut circuit, &mut nets, diode, first, &[node1, node2])?;
+            }
+            'q' => {
+                // Q collector base emitter model
+                if rest.len() < 2 {
+                    return Err(SpiceError::new(first, format!("{} needs three nodes and a model", first.text)));
+                }
+                let model = find_model(&models, &rest[1], &["NPN", "PNP"])?;
+                expect_end(&rest[2..])?;
+                let bjt_type = if model.kind == "NPN" { BjtType::Npn } else { BjtType::Pnp };
+                let mut bjt = Bjt::new(name, bjt_type);
+                bjt.saturation_current = model.parameter("is", bjt.saturation_current);
+                bjt.forward_beta = model.parameter("bf", bjt.forward_beta);
+                bjt.reverse_beta = model.parameter("br", bjt.reverse_beta);
+                bjt.early_voltage = model.parameters.get("vaf").copied();
+                add(&mut circuit, &mut nets, bjt, first, &[node1, node2, &rest[0]])?;
+            }
+            'm' => {
+                // M drain gate source body model [W=width] [L=length]
+                if rest.len() < 3 {
+                    return Err(SpiceError::new(first, format!("{} needs four nodes and a model", first.text)));
+                }
+                let model = find_model(&models, &rest[2], &["NMOS", "PMOS"])?;
+                if model.parameter("level", 1.0) != 1.0 {
+                    return Err(SpiceError::new(&rest[2], "only level 1 MOSFET models are supported"));
+                }
+                let mosfet_type = if model.kind == "NMOS" { MosfetType::Nmos } else { MosfetType::Pmos };
+                let mut mosfet = Mosfet::new(name, mosfet_type);
+                mosfet.threshold_voltage = model.parameter("vto", mosfet.threshold_voltage);
+                mosfet.transconductance = model.parameter("kp", mosfet.transconductance);
+                mosfet.channel_length_modulation = model.parameter("lambda", mosfet.channel_length_modulation);
+                mosfet.body_effect = model.parameter("gamma", mosfet.body_effect);
+                mosfet.surface_potential = model.parameter("phi", mosfet.surface_potential);
+                for (key, value) in parameters(&rest[3..])? {
+                    match key.text.to_lowercase().as_str() {
+                        "w" => mosfet.width = value,
+                        "l" => mosfet.length = value,
+                        _ => return Err(SpiceError::new(key, format!("unexpected {}", key.text))),
+                    }
+                }
+                add(&mut circuit, &mut nets, mosfet, first, &[node1, &rest[0], node2, &rest[1]])?;
+            }
+            _ => return Err(SpiceError::new(first, format!("unsupported element {}", first.text))),
+        }
+    }
+
+    Ok(circuit)
+}
+
+// A .model card: the device kind (D, NPN, PNP, NMOS or PMOS) and its parameters by lowercase name
+struct Model {
+    kind: String,
+    parameters: HashMap<String, f64>,
+}
+impl Model {
+    fn parameter(&self, name: &str, default: f64) -> f64 {
+        self.parameters.get(name).copied().unwrap_or(default)
+    }
+}
+
+// collect the .model cards up to .end by lowercase model name
+fn models(statements: &[Vec<Token>]) -> Result<HashMap<String, Model>, SpiceError> {
+    let mut models = HashMap::new();
+
+    for statement in statements {
+        let first = &statement[0];
+        let keyword = first.text.to_lowercase();
+        if keyword == ".end" {
+            break;
+        }
+        if keyword != ".model" {
+            continue;
+        }
+
+        let (Some(name), Some(kind)) = (statement.get(1), statement.get(2)) else {
+            return Err(SpiceError::new(first, ".model needs a name and a type"));
+        };
+        let kind_text = kind.text.to_uppercase();
+        if !["D", "NPN", "PNP", "NMOS", "PMOS"].contains(&kind_text.as_str()) {
+            return Err(SpiceError::new(kind, format!("unsupported model type {}", kind.text)));
+        }
+        let parameters = parameters(&statement[3..])?
+            .into_iter()
+            .map(|(key, value)| (key.text.to_lowercase(), value))
+            .collect();
+        let model = Model {
+            kind: kind_text,
+            parameters,
+        };
+        if models.insert(name.text.to_lowercase(), model).is_some() {
+            return Err(SpiceError::new(name, format!("model {} is defined twice", name.text)));
+        }
+    }
+
+    Ok(models)
+}
+
+// the model an element refers to, which must be one of the given kinds
+fn find_model<'a>(models: &'a HashMap<String, Model>, name: &Token, kinds: &[&str]) -> Result<&'a Model, SpiceError> {
+    let model = models
+        .get(&name.text.to_lowercase())
+        .ok_or_else(|| SpiceError::new(name, format!("no model named {}", name.text)))?;
+    if !kinds.contains(&model.kind.as_str()) {
+        return Err(SpiceError::new(name, format!("{} is a {} model", name.text, model.kind)));
+    }
+    Ok(model)
+}
+
+// parse a list of name=value parameters
+fn parameters(tokens: &[Token]) -> Result<Vec<(&Token, f64)>, SpiceError> {
+    let mut parameters = Vec::new();
+    let mut rest = tokens;
+    loop {
+        match rest {
+            [] => return Ok(parameters),
+            [key, equals, value_token, tail @ ..] if equals.text == "=" => {
+                parameters.push((key, value(value_token)?));
+                rest = tail;
+            }
+            [token, ..] => return Err(SpiceError::new(token, format!("expected a parameter, found {}", token.text))),
+        }
+    }
+}
+
+// Parse a number with an optional engineering suffix, e.g. 4.7k, 10u, 2meg or 1e-3.
+// Any letters after the suffix are treated as units and ignored (10uF, 5V).
+pub fn parse_value(text: &str) -> Option<f64> {
+    let text = text.to_lowercase();
+
+    // find the longest prefix that is a valid number
+    let number_end = (1..=text.len())
+        .rev()
+        .filter(|&end| text.is_char_boundary(end))
+        .find(|&end| {
+            let prefix = &text[..end];
+            // rust accepts "inf" and "nan" which are not SPICE numbers
+            prefix.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '-' || c == '+')
+                && prefix.parse::<f64>().is_ok()
+        })?;
+    let number: f64 = text[..number_end].parse().ok()?;
+    let suffix = &text[number_end..];
+
+    if !suffix.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    if suffix.starts_with("mil") {
+        return Some(number * 25.4e-6);
+    }
+    let exponent = if suffix.starts_with("meg") {
+        6
+    } else {
+        match suffix.chars().next() {
+            Some('t') => 12,
+            Some('g') => 9,
+            Some('k') => 3,
+            Some('m') => -3,
+            Some('u') => -6,
+            Some('n') => -9,
+            Some('p') => -12,
+            Some('f') => -15,
+            _ => 0,
+        }
+    };
+
+    // apply the suffix as a decimal exponent so that 10u is exactly 1e-5
+    if exponent == 0 || text[..number_end].contains('e') {
+        Some(number * 10f64.powi(exponent))
+    } else {
+        format!("{}e{}", &text[..number_end], exponent).parse().ok()
+    }
+}
+
+// split the netlist into statements, joining continuation lines and dropping the title and comments
+fn statements(netlist: &str) -> Vec<Vec<Token>> {
+    let mut statements: Vec<Vec<Token>> = Vec::new();
+
+    for (index, line) in netlist.lines().enumerate().skip(1) {
+        let line_number = index + 1;
+        let content = match line.find(';') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        if content.trim_start().starts_with('*') {
+            continue;
+        }
+
+        let mut tokens = tokenize(content, line_number);
+        if tokens.is_empty() {
+            continue;
+        }
+
+        if tokens[0].text.starts_with('+') {
+            // continuation of the previous statement
+            tokens[0].text.remove(0);
+            tokens[0].column += 1;
+            if tokens[0].text.is_empty() {
+                tokens.remove(0);
+            }
+            if let Some(previous) = statements.last_mut() {
+                previous.extend(tokens);
+                continue;
+            }
+        }
+        if !tokens.is_empty() {
+            statements.push(tokens);
+        }
+    }
+
+    statements
+}
+
+// split a line on whitespace, commas, parentheses and around '=', keeping the position of every token
+fn tokenize(line: &str, line_number: usize) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut current: Option<Token> = None;
+
+    for (index, c) in line.char_indices() {
+        let column = line[..index].chars().count() + 1;
+        if c.is_whitespace() || c == ',' || c == '(' || c == ')' || c == '=' {
+            if let Some(token) = current.take() {
+                tokens.push(token);
+            }
+            if c == '=' {
+                tokens.push(Token {
+                    text: "=".to_string(),
+                    line: line_number,
+                    column,
+                });
+            }
+        } else {
+            match current.as_mut() {
+                Some(token) => token.text.push(c),
+                None => {
+                    current = Some(Token {
+                        text: c.to_string(),
+                        line: line_number,
+                        column,
+                    })
+                }
+            }
+        }
+    }
+    if let Some(token) = current {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+fn value(token: &Token) -> Result<f64, SpiceError> {
+    parse_value(&token.text).ok_or_else(|| SpiceError::new(token, format!("invalid value {}", token.text)))
+}
+
+fn expect_end(tokens: &[Token]) -> Result<(), SpiceError> {
+    match tokens.first() {
+        Some(token) => Err(SpiceError::new(token, format!("unexpected {}", token.text))),
+        None => Ok(()),
+    }
+}
+
+// parse an optional IC=value after a capacitor or inductor value
+fn initial_condition(tokens: &[Token]) -> Result<Option<f64>, SpiceError> {
+    match tokens {
+        [] => Ok(None),
+        [key, equals, ic, rest @ ..] if key.text.eq_ignore_ascii_case("ic") && equals.text == "=" => {
+            expect_end(rest)?;
+            Ok(Some(value(ic)?))
+        }
+        [token, ..] => Err(SpiceError::new(token, format!("unexpected {}", token.text))),
+    }
+}
+
+struct SourceValues {
+    dc: f64,
+    ac_magnitude: f64,
+    ac_phase: f64,
+    waveform: Option<Waveform>,
+}
+
+// parse the value of an independent source: [DC] value [AC magnitude [phase]] [waveform]
+fn source_values(tokens: &[Token]) -> Result<SourceValues, SpiceError> {
+    let mut source = SourceValues {
+        dc: 0.0,
+        ac_magnitude: 0.0,
+        ac_phase: 0.0,
+        waveform: None,
+    };
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        match token.text.to_lowercase().as_str() {
+            "dc" => {
+                let dc = tokens.get(i + 1).ok_or_else(|| SpiceError::new(token, "DC needs a value"))?;
+                source.dc = value(dc)?;
+                i += 2;
+            }
+            "ac" => {
+                source.ac_magnitude = 1.0;
+                i += 1;
+                if let Some(magnitude) = tokens.get(i).filter(|t| parse_value(&t.text).is_some()) {
+                    source.ac_magnitude = value(magnitude)?;
+                    i += 1;
+                    if let Some(phase) = tokens.get(i).filter(|t| parse_value(&t.text).is_some()) {
+                        source.ac_phase = value(phase)?;
+                        i += 1;
+                    }
+                }
+            }
+            "sin" | "pulse" | "pwl" | "exp" => {
+                let count = tokens[i + 1..].iter().take_while(|t| parse_value(&t.text).is_some()).count();
+                let values: Vec<f64> = tokens[i + 1..i + 1 + count].iter().map(value).collect::<Result<_, _>>()?;
+                source.waveform = Some(waveform(token, &values)?);
+                i += 1 + count;
+            }
+            _ if i == 0 => {
+                source.dc = value(token)?;
+                i += 1;
+            }
+            _ => return Err(SpiceError::new(token, format!("unexpected {}", token.text))),
+        }
+    }
+
+    Ok(source)
+}
+
+// build a source function from its name and parameters, using the SPICE defaults for the
+// optional parameters that don't depend on the simulation time step
+fn waveform(name: &Token, values: &[f64]) -> Result<Waveform, SpiceError> {
+    let kind = name.text.to_uppercase();
+    let (required, allowed) = match kind.as_str() {
+        "SIN" => (3, 6),
+        "PULSE" => (2, 7),
+        "EXP" => (4, 6),
+        _ => (2, usize::MAX),
+    };
+    if values.len() < required || values.len() > allowed {
+        return Err(SpiceError::new(name, format!("{} has the wrong number of parameters", kind)));
+    }
+    let optional = |index: usize, default: f64| values.get(index).copied().unwrap_or(default);
+
+    let waveform = match kind.as_str() {
+        "SIN" => Waveform::Sin {
+            offset: values[0],
+            amplitude: values[1],
+            frequency: values[2],
+            delay: optional(3, 0.0),
+            damping: optional(4, 0.0),
+            phase: optional(5, 0.0),
+        },
+        "PULSE" => Waveform::Pulse {
+            initial: values[0],
+            pulsed: values[1],
+            delay: optional(2, 0.0),
+            rise: optional(3, 0.0),
+            fall: optional(4, 0.0),
+            width: optional(5, f64::INFINITY),
+            period: values.get(6).copied(),
+        },
+        "EXP" => Waveform::Exp {
+            initial: values[0],
+            pulsed: values[1],
+            rise_delay: values[2],
+            rise_time_constant: values[3],
+            fall_delay: optional(4, f64::INFINITY),
+            fall_time_constant: optional(5, values[3]),
+        },
+        _ => {
+            if !values.len().is_multiple_of(2) {
+                return Err(SpiceError::new(name, "PWL needs pairs of times and values"));
+            }
+            let points: Vec<(f64, f64)> = values.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+            if points.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+                return Err(SpiceError::new(name, "PWL times must not decrease"));
+            }
+            Waveform::Pwl(points)
+        }
+    };
+
+    Ok(waveform)
+}
+
+// nets are case insensitive and "gnd" is another name for "0"
+fn net_key(net: &Token) -> String {
+    let key = net.text.to_lowercase();
+    if key == "gnd" {
+        "0".to_string()
+    } else {
+        key
+    }
+}
+
+// add a component to the circuit and attach its terminals to the named nets
+fn add(
+    circuit: &mut Circuit,
+    nets: &mut HashMap<String, usize>,
+    component: impl Component + 'static,
+    name: &Token,
+    terminals: &[&Token],
+) -> Result<(), SpiceError> {
+    let error = |token: &Token, e: CircuitError| SpiceError::new(token, e.to_string());
+
+    circuit.add_component(component).map_err(|e| error(name, e))?;
+    let nodes = circuit.get_component(&name.text).unwrap().component().nodes.clone();
+
+    for (node, net) in nodes.into_iter().zip(terminals) {
+        let key = net_key(net);
+        match nets.get(&key) {
+            Some(&existing) => {
+                circuit.connect(existing, node).map_err(|e| error(net, e))?;
+            }
+            None => {
+                nets.insert(key.clone(), node);
+                circuit.nodes[node].label = Some(net.text.clone());
+                if key == "0" {
+                    circuit.set_ground(node).map_err(|e| error(net, e))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// SPICE netlist writer
+impl Circuit {
+    // Serialize the circuit as a SPICE netlist that parse_spice (and other simulators) can read.
+    // Ground nets are written as "0", labelled nets keep their label and every other net is
+    // named after its net id. Element names are prefixed with their SPICE letter when needed,
+    // and every diode and transistor gets a .model card of its own. Components SPICE can't
+    // express, such as ideal or saturating op amps, are reported as UnsupportedComponent.
+    pub fn to_spice(&self) -> Result<String, CircuitError> {
+        let net_map = self.net_map();
+        let net_names = self.spice_net_names(&net_map);
+        let net = |node: Option<usize>| match node {
+            Some(node) => net_names[net_map[node]].clone(),
+            None => "?".to_string(),
+        };
+
+        let mut netlist = String::from("circuit-solver netlist\n");
+        let mut models = Vec::new();
+
+        let mut names: Vec<&String> = self.components.keys().collect();
+        names.sort();
+        for name in names {
+            let component = self.components[name].as_ref();
+            let base = component.component();
+            let any = component.as_any();
+
+            let line = if let Some(resistor) = any.downcast_ref::<Resistor>() {
+                format!("{} {} {} {}", element_name('R', name), net(base.node1()), net(base.node2()), value_text(resistor.resistance))
+            } else if let Some(capacitor) = any.downcast_ref::<Capacitor>() {
+                let mut line = format!("{} {} {} {}", element_name('C', name), net(base.node1()), net(base.node2()), value_text(capacitor.capacitance));
+                if let Some(ic) = capacitor.initial_voltage {
+                    line.push_str(&format!(" IC={}", value_text(ic)));
+                }
+                line
+            } else if let Some(inductor) = any.downcast_ref::<Inductor>() {
+                let mut line = format!("{} {} {} {}", element_name('L', name), net(base.node1()), net(base.node2()), value_text(inductor.inductance));
+                if let Some(ic) = inductor.initial_current {
+                    line.push_str(&format!(" IC={}", value_text(ic)));
+                }
+                line
+            } else if let Some(source) = any.downcast_ref::<VoltageSource>() {
+                let mut line = format!(
+                    "{} {} {} DC {}",
+                    element_name('V', name),
+                    net(source.positive_node()),
+                    net(source.negative_node()),
+                    value_text(source.voltage)
+                );
+                if source.ac_magnitude != 0.0 {
+                    line.push_str(&format!(" AC {} {}", value_text(source.ac_magnitude), value_text(source.ac_phase)));
+                }
+                if let Some(waveform) = &source.waveform {
+                    line.push_str(&format!(" {}", waveform_text(waveform)));
+                }
+                line
+            } else if let Some(source) = any.downcast_ref::<CurrentSource>() {
+                // SPICE current sources push current from the first node through the source to the second
+                let mut line = format!(
+                    "{} {} {} DC {}",
+                    element_name('I', name),
+                    net(source.input_node()),
+                    net(source.output_node()),
+                    value_text(source.current)
+                );
+                if source.ac_magnitude != 0.0 {
+                    line.push_str(&format!(" AC {} {}", value_text(source.ac_magnitude), value_text(source.ac_phase)));
+                }
+                if let Some(waveform) = &source.waveform {
+                    line.push_str(&format!(" {}", waveform_text(waveform)));
+                }
+                line
+            } else if let Some(source) = any.downcast_ref::<Vcvs>() {
+                format!(
+                    "{} {} {} {} {} {}",
+                    element_name('E', name),
+                    net(base.node1()),
+                    net(base.node2()),
+                    net(base.node(2)),
+                    net(base.node(3)),
+                    value_text(source.gain)
+                )
+            } else if let Some(source) = any.downcast_ref::<Vccs>() {
+                // the output current leaves node1, so it is the second SPICE node
+                format!(
+                    "{} {} {} {} {} {}",
+                    element_name('G', name),
+                    net(base.node2()),
+                    net(base.node1()),
+                    net(base.node(2)),
+                    net(base.node(3)),
+                    value_text(source.transconductance)
+                )
+            } else if let Some(source) = any.downcast_ref::<Ccvs>() {
+                match self.spice_control(&source.control) {
+                    Some((control, sign)) => format!(
+                        "{} {} {} {} {}",
+                        element_name('H', name),
+                        net(base.node1()),
+                        net(base.node2()),
+                        control,
+                        value_text(sign * source.transresistance)
+                    ),
+                    None => return Err(voltage_source_control(name, &source.control)),
+                }
+            } else if let Some(source) = any.downcast_ref::<Cccs>() {
+                match self.spice_control(&source.control) {
+                    Some((control, sign)) => format!(
+                        "{} {} {} {} {}",
+                        element_name('F', name),
+                        net(base.node2()),
+                        net(base.node1()),
+                        control,
+                        value_text(sign * source.gain)
+                    ),
+                    None => return Err(voltage_source_control(name, &source.control)),
+                }
+            } else if let Some(diode) = any.downcast_ref::<Diode>() {
+                let element = element_name('D', name);
+                let mut parameters = vec![
+                    ("IS", diode.saturation_current),
+                    ("N", diode.emission_coefficient),
+                    ("RS", diode.series_resistance),
+                ];
+                parameters.extend(diode.breakdown_voltage.map(|bv| ("BV", bv)));
+                models.push(model_card(&element, "D", &parameters));
+                format!("{} {} {} {}_model", element, net(base.node1()), net(base.node2()), element)
+            } else if let Some(bjt) = any.downcast_ref::<Bjt>() {
+                let element = element_name('Q', name);
+                let kind = match bjt.bjt_type {
+                    BjtType::Npn => "NPN",
+                    BjtType::Pnp => "PNP",
+                };
+                let mut parameters = vec![("IS", bjt.saturation_current), ("BF", bjt.forward_beta), ("BR", bjt.reverse_beta)];
+                parameters.extend(bjt.early_voltage.map(|vaf| ("VAF", vaf)));
+                models.push(model_card(&element, kind, &parameters));
+                format!("{} {} {} {} {}_model", element, net(bjt.collector()), net(bjt.base()), net(bjt.emitter()), element)
+            } else if let Some(mosfet) = any.downcast_ref::<Mosfet>() {
+                let element = element_name('M', name);
+                let kind = match mosfet.mosfet_type {
+                    MosfetType::Nmos => "NMOS",
+                    MosfetType::Pmos => "PMOS",
+                };
+                let parameters = [
+                    ("LEVEL", 1.0),
+                    ("VTO", mosfet.threshold_voltage),
+                    ("KP", mosfet.transconductance),
+                    ("LAMBDA", mosfet.channel_length_modulation),
+                    ("GAMMA", mosfet.body_effect),
+                    ("PHI", mosfet.surface_potential),
+                ];
+                models.push(model_card(&element, kind, &parameters));
+                format!(
+                    "{} {} {} {} {} {}_model W={} L={}",
+                    element,
+                    net(mosfet.drain()),
+                    net(mosfet.gate()),
+                    net(mosfet.source()),
+                    net(mosfet.body()),
+                    element,
+                    value_text(mosfet.width),
+                    value_text(mosfet.length)
+                )
+            } else if let Some(op_amp) = any.downcast_ref::<OpAmp>() {
+                // only a plain finite gain op amp is a voltage controlled voltage source
+                let reason = if op_amp.gain.is_none() {
+                    Some("SPICE has no ideal op amp")
+                } else if op_amp.rails.is_some() {
+                    Some("SPICE has no op amp with output rails")
+                } else if op_amp.gain_bandwidth.is_some() {
+                    Some("SPICE has no op amp with a gain bandwidth product")
+                } else if op_amp.input_resistance.is_some() || op_amp.output_resistance != 0.0 {
+                    Some("SPICE has no op amp with input or output resistance")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return Err(CircuitError::UnsupportedComponent {
+                        component: name.clone(),
+                        reason: reason.to_string(),
+                    });
+                }
+                format!(
+                    "{} {} {} {} {} {}",
+                    element_name('E', name),
+                    net(base.node(0)),
+                    net(base.node(1)),
+                    net(base.node(2)),
+                    net(base.node(3)),
+                    value_text(op_amp.gain.unwrap_or_default())
+                )
+            } else {
+                return Err(CircuitError::UnsupportedComponent {
+                    component: name.clone(),
+                    reason: "it has no SPICE equivalent".to_string(),
+                });
+            };
+
+            netlist.push_str(&line);
+            netlist.push('\n');
+        }
+
+        for model in models {
+            netlist.push_str(&model);
+            netlist.push('\n');
+        }
+        netlist.push_str(".end\n");
+        Ok(netlist)
+    }
+
+    // SPICE current controls must be voltage sources, whose current is measured from the
+    // positive to the negative terminal. Returns the SPICE name of the control and the sign
+    // that converts our node1 to node2 current into the SPICE one.
+    fn spice_control(&self, control: &str) -> Option<(String, f64)> {
+        let source = self.components.get(control)?.as_any().downcast_ref::<VoltageSource>()?;
+        let sign = if source.positive_node() == source.component.node1() { 1.0 } else { -1.0 };
+        Some((element_name('V', control), sign))
+    }
+
+    // pick a unique SPICE name for every net
+    fn spice_net_names(&self, net_map: &[usize]) -> Vec<String> {
+        let net_count = net_map.iter().map(|net| net + 1).max().unwrap_or(0);
+        let mut names: Vec<Option<String>> = vec![None; net_count];
+
+        // grounds first so that no label can take the name "0"
+        for node in self.nodes.iter().filter(|node| node.ground) {
+            names[net_map[node.id]] = Some("0".to_string());
+        }
+
+        let mut used: Vec<String> = vec!["0".to_string(), "gnd".to_string()];
+        for node in &self.nodes {
+            let net = net_map[node.id];
+            if names[net].is_some() {
+                continue;
+            }
+            if let Some(label) = &node.label {
+                let label: String = label
+                    .chars()
+                    .map(|c| if c.is_whitespace() || "=,();*+".contains(c) { '_' } else { c })
+                    .collect();
+                if !label.is_empty() && !used.contains(&label.to_lowercase()) {
+                    used.push(label.to_lowercase());
+                    names[net] = Some(label);
+                }
+            }
+        }
+
+        names
+            .into_iter()
+            .enumerate()
+            .map(|(net, name)| {
+                name.unwrap_or_else(|| {
+                    let mut name = format!("n{}", net);
+                    while used.contains(&name) {
+                        name.push('_');
+                    }
+                    used.push(name.clone());
+                    name
+                })
+            })
+            .collect()
+    }
+}
+
+fn voltage_source_control(name: &str, control: &str) -> CircuitError {
+    CircuitError::UnsupportedComponent {
+        component: name.to_string(),
+        reason: format!("it is controlled by {}, which SPICE requires to be a voltage source", control),
+    }
+}
+
+// a .model card named after the element it belongs to
+fn model_card(element: &str, kind: &str, parameters: &[(&str, f64)]) -> String {
+    let parameters: Vec<String> = parameters
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value_text(*value)))
+        .collect();
+    format!(".model {}_model {}({})", element, kind, parameters.join(" "))
+}
+
+// SPICE identifies the element type by the first letter of its name
+fn element_name(letter: char, name: &str) -> String {
+    if name.to_uppercase().starts_with(letter) {
+        name.to_string()
+    } else {
+        format!("{}{}", letter, name)
+    }
+}
+
+// write a source function, leaving out trailing parameters that are at their defaults
+fn waveform_text(waveform: &Waveform) -> String {
+    let (name, mut values) = match waveform {
+        Waveform::Sin { offset, amplitude, frequency, delay, damping, phase } => {
+            ("SIN", vec![*offset, *amplitude, *frequency, *delay, *damping, *phase])
+        }
+        Waveform::Pulse { initial, pulsed, delay, rise, fall, width, period } => {
+            let mut values = vec![*initial, *pulsed, *delay, *rise, *fall];
+            // SPICE has no infinite width, so a repeating pulse that never ends is written as
+            // wide as its period, and one that doesn't repeat is written without a width
+            if width.is_finite() {
+                values.push(*width);
+                values.extend(period);
+            } else if let Some(period) = period.filter(|period| *period > 0.0) {
+                values.extend([period, period]);
+            }
+            ("PULSE", values)
+        }
+        Waveform::Pwl(points) => ("PWL", points.iter().flat_map(|(time, value)| [*time, *value]).collect()),
+        Waveform::Exp { initial, pulsed, rise_delay, rise_time_constant, fall_delay, fall_time_constant } => {
+            let mut values = vec![*initial, *pulsed, *rise_delay, *rise_time_constant];
+            if fall_delay.is_finite() {
+                values.extend([*fall_delay, *fall_time_constant]);
+            }
+            ("EXP", values)
+        }
+    };
+    if name == "SIN" {
+        while values.len() > 3 && values.last() == Some(&0.0) {
+            values.pop();
+        }
+    }
+
+    let values: Vec<String> = values.into_iter().map(value_text).collect();
+    format!("{}({})", name, values.join(" "))
+}
+
+// write very small and very large values in exponent notation
+fn value_text(value: f64) -> String {
+    if value != 0.0 && (value.abs() < 1e-3 || value.abs() >= 1e9) {
+        format!("{:e}", value)
+    } else {
+        format!("{}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(netlist: &str) -> SpiceError {
+        match parse_spice(netlist) {
+            Ok(_) => panic!("expected the netlist to be rejected"),
+            Err(error) => error,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    #[test]
+    fn parses_engineering_suffixes() {
+        assert_eq!(parse_value("4.7k"), Some(4700.0));
+        assert_eq!(parse_value("10u"), Some(1e-5));
+        assert_eq!(parse_value("2meg"), Some(2e6));
+        assert_eq!(parse_value("2MEG"), Some(2e6));
+        assert_eq!(parse_value("3m"), Some(3e-3));
+        assert_eq!(parse_value("1t"), Some(1e12));
+        assert_eq!(parse_value("5g"), Some(5e9));
+        assert_eq!(parse_value("22n"), Some(22e-9));
+        assert_eq!(parse_value("100p"), Some(100e-12));
+        assert_eq!(parse_value("1f"), Some(1e-15));
+        assert!(close(parse_value("1mil").unwrap(), 25.4e-6));
+        assert_eq!(parse_value("1e-3"), Some(1e-3));
+        assert_eq!(parse_value("-2.5"), Some(-2.5));
+    }
+
+    #[test]
+    fn ignores_units_after_the_suffix() {
+        assert_eq!(parse_value("10uF"), Some(1e-5));
+        assert_eq!(parse_value("5V"), Some(5.0));
+        assert_eq!(parse_value("1kohm"), Some(1000.0));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert_eq!(parse_value("abc"), None);
+        assert_eq!(parse_value("inf"), None);
+        assert_eq!(parse_value("nan"), None);
+        assert_eq!(parse_value("1k2"), None);
+        assert_eq!(parse_value(""), None);
+    }
+
+    #[test]
+    fn builds_and_solves_a_divider() {
+        let mut circuit = parse_spice("divider\nV1 in 0 10\nR1 in out 1k\nR2 out gnd 3k\n.end\n").unwrap();
+        circuit.solve_dc().unwrap();
+        assert!(close(circuit.net_voltage("in").unwrap(), 10.0));
+        assert!(close(circuit.net_voltage("out").unwrap(), 7.5));
+        // "gnd" is the same net as "0"
+        assert_eq!(circuit.net_of(circuit.net_node("gnd").unwrap()), circuit.net_of(circuit.net_node("0").unwrap()));
+    }
+
+    #[test]
+    fn current_flows_from_the_first_node_through_the_source() {
+        let mut circuit = parse_spice("source\nI1 0 a 2m\nR1 a 0 1k\n").unwrap();
+        circuit.solve_dc().unwrap();
+        assert!(close(circuit.net_voltage("a").unwrap(), 2.0));
+    }
+
+    #[test]
+    fn nets_are_case_insensitive() {
+        let circuit = parse_spice("case\nV1 OUT 0 1\nR1 out 0 1\n").unwrap();
+        let (v1, r1) = (circuit.get_component("V1").unwrap(), circuit.get_component("R1").unwrap());
+        assert_eq!(circuit.net_of(v1.component().nodes[0]), circuit.net_of(r1.component().nodes[0]));
+    }
+
+    #[test]
+    fn skips_the_title_comments_and_dot_commands() {
+        let netlist = "R1 a b 1\n* a comment\nR2 a 0 1 ; trailing comment\n.op\nR3 a 0\n+ 2\n.end\nR4 a 0 1\n";
+        let circuit = parse_spice(netlist).unwrap();
+        let mut names: Vec<&String> = circuit.components.keys().collect();
+        names.sort();
+        assert_eq!(names, ["R2", "R3"]);
+        let r3 = circuit.get_component("R3").unwrap().as_any().downcast_ref::<Resistor>().unwrap();
+        assert_eq!(r3.resistance, 2.0);
+    }
+
+    #[test]
+    fn reads_initial_conditions_and_source_functions() {
+        let netlist = "t\nC1 a 0 1u IC=2\nL1 a b 1m ic = 0.5\nV1 b 0 DC 1 AC 2 45 SIN(0 1 1k)\nI1 a 0 PULSE(0 1 0 1n 1n 1u 2u)\n";
+        let circuit = parse_spice(netlist).unwrap();
+        let capacitor = circuit.get_component("C1").unwrap().as_any().downcast_ref::<Capacitor>().unwrap();
+        assert_eq!(capacitor.initial_voltage, Some(2.0));
+        let inductor = circuit.get_component("L1").unwrap().as_any().downcast_ref::<Inductor>().unwrap();
+        assert_eq!(inductor.initial_current, Some(0.5));
+        let source = circuit.get_component("V1").unwrap().as_any().downcast_ref::<VoltageSource>().unwrap();
+        assert_eq!((source.voltage, source.ac_magnitude, source.ac_phase), (1.0, 2.0, 45.0));
+        assert!(matches!(source.waveform, Some(Waveform::Sin { frequency, .. }) if frequency == 1e3));
+        let source = circuit.get_component("I1").unwrap().as_any().downcast_ref::<CurrentSource>().unwrap();
+        assert!(matches!(source.waveform, Some(Waveform::Pulse { period: Some(period), .. }) if period == 2e-6));
+    }
+
+    #[test]
+    fn reports_the_line_and_column_of_errors() {
+        let error = rejected("title\nR1 a 0 1k\n  R2 a 0 oops\n");
+        assert_eq!((error.line, error.column), (3, 10));
+        assert_eq!(error.message, "invalid value oops");
+
+        let error = rejected("title\nX1 a 0 1\n");
+        assert_eq!((error.line, error.column), (2, 1));
+
+        let error = rejected("title\nR1 a 0 1\nR1 a 0 2\n");
+        assert_eq!((error.line, error.column), (3, 1));
+
+        let error = rejected("title\nR1 a 0\n");
+        assert_eq!((error.line, error.column), (2, 1));
+
+        let error = rejected("title\nC1 a 0 1u IC=2 extra\n");
+        assert_eq!((error.line, error.column), (2, 16));
+    }
+
+    #[test]
+    fn writes_netlists_that_read_back_the_same() {
+        let netlist = "t\nV1 in 0 DC 5 AC 1 0\nR1 in out 4.7k\nC1 out 0 10n IC=1\nL1 out x 1m\nI1 x 0 1m\nE1 e 0 in out 2\nR2 e 0 1\n.end\n";
+        let written = parse_spice(netlist).unwrap().to_spice().unwrap();
+        assert_eq!(parse_spice(&written).unwrap().to_spice().unwrap(), written);
+    }
+
+    #[test]
+    fn reads_diodes_from_model_cards_in_any_order() {
+        let netlist = "t\nD1 a k dmod\nD2 k 0 DMOD\n.model dmod D(IS=1e-12 N=2 BV=5.1 CJO=1p)\n";
+        let circuit = parse_spice(netlist).unwrap();
+        for name in ["D1", "D2"] {
+            let diode = circuit.get_component(name).unwrap().as_any().downcast_ref::<Diode>().unwrap();
+            assert_eq!((diode.saturation_current, diode.emission_coefficient), (1e-12, 2.0));
+            assert_eq!((diode.series_resistance, diode.breakdown_voltage), (0.0, Some(5.1)));
+        }
+    }
+
+    #[test]
+    fn rejects_missing_and_malformed_models() {
+        let error = rejected("t\nD1 a 0 nothing\n");
+        assert_eq!((error.line, error.column, error.message.as_str()), (2, 8, "no model named nothing"));
+
+        let error = rejected("t\n.model x D(IS)\n");
+        assert_eq!((error.line, error.column), (2, 12));
+
+        let error = rejected("t\n.model x Z(IS=1)\n");
+        assert_eq!(error.message, "unsupported model type Z");
+
+        let error = rejected("t\n.model x D\n.model X D\n");
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn writes_diodes_that_read_back_the_same() {
+        let netlist = "t\nV1 in 0 5\nR1 in a 1k\nD1 a 0 dz\n.model dz D(IS=1e-14 RS=2 BV=4.7)\n";
+        let circuit = parse_spice(netlist).unwrap();
+        let written = circuit.to_spice().unwrap();
+        assert!(written.contains("D1 a 0 D1_model\n"));
+        assert!(written.contains(".model D1_model D(IS=1e-14 N=1 RS=2 BV=4.7)\n"));
+        assert_eq!(parse_spice(&written).unwrap().to_spice().unwrap(), written);
+    }
+
+    #[test]
+    fn reads_bjts_with_their_nodes_in_spice_order() {
+        let circuit = parse_spice("t\nQ1 c b e qmod\n.model qmod PNP(BF=50 VAF=80)\n").unwrap();
+        let bjt = circuit.get_component("Q1").unwrap().as_any().downcast_ref::<Bjt>().unwrap();
+        assert_eq!(bjt.bjt_type, BjtType::Pnp);
+        assert_eq!((bjt.forward_beta, bjt.reverse_beta, bjt.early_voltage), (50.0, 1.0, Some(80.0)));
+        let label = |node: Option<usize>| circuit.nodes[node.unwrap()].label.clone().unwrap();
+        assert_eq!([label(bjt.collector()), label(bjt.base()), label(bjt.emitter())], ["c", "b", "e"]);
+
+        let error = rejected("t\nQ1 c b 0 dmod\n.model dmod D\n");
+        assert_eq!(error.message, "dmod is a D model");
+    }
+
+    #[test]
+    fn writes_bjts_that_read_back_the_same() {
+        let netlist = "t\nV1 in 0 5\nR1 in b 100k\nR2 in c 1k\nQ1 c b 0 qn\n.model qn NPN(BF=200 VAF=100)\n";
+        let mut circuit = parse_spice(netlist).unwrap();
+        let written = circuit.to_spice().unwrap();
+        assert!(written.contains("Q1 c b 0 Q1_model\n"));
+        assert!(written.contains(".model Q1_model NPN(IS=1e-16 BF=200 BR=1 VAF=100)\n"));
+
+        let mut read_back = parse_spice(&written).unwrap();
+        assert_eq!(read_back.to_spice().unwrap(), written);
+        circuit.solve_dc().unwrap();
+        read_back.solve_dc().unwrap();
+        assert!(close(circuit.net_voltage("c").unwrap(), read_back.net_voltage("c").unwrap()));
+    }
+
+    #[test]
+    fn reads_level_1_mosfets() {
+        let circuit = parse_spice("t\nM1 d g s 0 nch W=10u L=2u\n.model nch NMOS(LEVEL=1 VTO=0.5 KP=1e-4)\n").unwrap();
+        let mosfet = circuit.get_component("M1").unwrap().as_any().downcast_ref::<Mosfet>().unwrap();
+        assert_eq!(mosfet.mosfet_type, MosfetType::Nmos);
+        assert_eq!((mosfet.threshold_voltage, mosfet.transconductance), (0.5, 1e-4));
+        assert_eq!((mosfet.width, mosfet.length), (10e-6, 2e-6));
+        let label = |node: Option<usize>| circuit.nodes[node.unwrap()].label.clone().unwrap();
+        assert_eq!([label(mosfet.drain()), label(mosfet.gate()), label(mosfet.source())], ["d", "g", "s"]);
+
+        let error = rejected("t\nM1 d g 0 0 pch\n.model pch PMOS(LEVEL=3)\n");
+        assert_eq!(error.message, "only level 1 MOSFET models are supported");
+
+        let error = rejected("t\nM1 d g 0 0 nch X=1\n.model nch NMOS\n");
+        assert_eq!(error.message, "unexpected X");
+    }
+
+    #[test]
+    fn writes_mosfets_that_read_back_the_same() {
+        let netlist = "t\nV1 in 0 5\nR1 in d 10k\nM1 d in 0 0 nm W=4u L=1u\n.model nm NMOS(VTO=1 LAMBDA=0.01)\n";
+        let mut circuit = parse_spice(netlist).unwrap();
+        let written = circuit.to_spice().unwrap();
+        assert!(written.contains("M1 d in 0 0 M1_model W=4e-6 L=1e-6\n"));
+        assert!(written.contains(".model M1_model NMOS(LEVEL=1 VTO=1 "));
+
+        let mut read_back = parse_spice(&written).unwrap();
+        assert_eq!(read_back.to_spice().unwrap(), written);
+        circuit.solve_dc().unwrap();
+        read_back.solve_dc().unwrap();
+        assert!(close(circuit.net_voltage("d").unwrap(), read_back.net_voltage("d").unwrap()));
+    }
+
+    #[test]
+    fn writes_source_functions_that_read_back_the_same() {
+        let pulse = |width: f64, period: Option<f64>| Waveform::Pulse {
+            initial: 0.0,
+            pulsed: 1.0,
+            delay: 1e-6,
+            rise: 1e-9,
+            fall: 1e-9,
+            width,
+            period,
+        };
+        let waveforms = [
+            pulse(1e-6, Some(2e-6)),
+            pulse(1e-6, None),
+            pulse(f64::INFINITY, None),
+            pulse(f64::INFINITY, Some(2e-6)),
+            Waveform::Sin { offset: 0.5, amplitude: 1.0, frequency: 1e3, delay: 0.0, damping: 0.0, phase: 0.0 },
+            Waveform::Pwl(vec![(0.0, 0.0), (1e-3, 5.0)]),
+        ];
+        for waveform in waveforms {
+            let mut source = VoltageSource::new("V1", 0.0, Polarity::Normal);
+            source.waveform = Some(waveform.clone());
+            let mut circuit = Circuit::new();
+            circuit.add_component(source).unwrap();
+            circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+            for (component, terminal, net) in [("V1", 1, "a"), ("V1", 2, "0"), ("R1", 1, "a"), ("R1", 2, "0")] {
+                circuit.attach(component, terminal, net).unwrap();
+            }
+
+            let written = circuit.to_spice().unwrap();
+            let read_back = parse_spice(&written).unwrap();
+            let source = read_back.get_component("V1").unwrap().as_any().downcast_ref::<VoltageSource>().unwrap();
+            let read = source.waveform.as_ref().unwrap();
+            for time in [0.0, 1.5e-6, 2.5e-6, 3.5e-6, 1e-3, 1.2e-3] {
+                assert_eq!(read.value(time), waveform.value(time), "{} at {}", written, time);
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_components_spice_cannot_express() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(OpAmp::ideal("U1")).unwrap();
+        let error = circuit.to_spice().unwrap_err();
+        assert!(matches!(error, CircuitError::UnsupportedComponent { component, .. } if component == "U1"));
+
+        let mut circuit = parse_spice("t\nV1 a 0 1\nR1 a 0 1\nH1 b 0 V1 2\nR2 b 0 1\n").unwrap();
+        circuit.components.get_mut("H1").unwrap().as_any_mut().downcast_mut::<Ccvs>().unwrap().control = "R1".to_string();
+        assert!(matches!(circuit.to_spice(), Err(CircuitError::UnsupportedComponent { .. })));
+
+        let mut op_amp = OpAmp::new("U2", 1e5);
+        op_amp.rails = Some((-5.0, 5.0));
+        let mut circuit = Circuit::new();
+        circuit.add_component(op_amp).unwrap();
+        let error = circuit.to_spice().unwrap_err();
+        assert!(matches!(error, CircuitError::UnsupportedComponent { reason, .. } if reason.contains("rails")));
+    }
+
+    #[test]
+    fn writes_finite_gain_op_amps_as_voltage_controlled_sources() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(OpAmp::new("U1", 1e5)).unwrap();
+        circuit.add_component(VoltageSource::new("V1", 1e-3, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        for (terminal, net) in [("output", "out"), ("reference", "0"), ("non_inverting", "in"), ("inverting", "0")] {
+            circuit.attach_terminal("U1", terminal, net).unwrap();
+        }
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("R1", 1, "out"), ("R1", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+
+        let written = circuit.to_spice().unwrap();
+        assert!(written.contains("EU1 out 0 in 0 100000\n"));
+        // the op amp reads back as a voltage controlled voltage source named EU1
+        let mut read_back = parse_spice(&written).unwrap();
+        assert!(read_back.to_spice().unwrap().contains("EU1 out 0 in 0 100000\n"));
+        read_back.solve_dc().unwrap();
+        assert!(close(read_back.net_voltage("out").unwrap(), 100.0));
+    }
+}
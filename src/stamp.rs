@@ -0,0 +1,153 @@
+// src/stamp.rs
+
+use crate::error::CircuitError;
+use crate::matrix::{LinearSystem, Scalar};
+use crate::mna::{Entry, Layout};
+use crate::transient::IntegrationMethod;
+
+// The analysis a component is being stamped for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Analysis {
+    Dc,
+    Ac { omega: f64 },
+    // the operating point a transient simulation starts from, where capacitors and inductors
+    // with an initial condition are forced to it when use_initial_conditions is set
+    TransientStart { use_initial_conditions: bool },
+    // a time point of a transient simulation, reached with a step of `step` seconds
+    TransientStep { time: f64, step: f64, method: IntegrationMethod },
+}
+impl Analysis {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Analysis::TransientStart { .. } | Analysis::TransientStep { .. })
+    }
+
+    pub fn time(&self) -> f64 {
+        match self {
+            Analysis::TransientStep { time, .. } => *time,
+            _ => 0.0,
+        }
+    }
+}
+
+// Gives a component access to the rows and columns of the MNA system that belong to it.
+// Terminals are addressed by their index in Component::terminals and branches by their
+// index from 0 to Component::branch_count.
+pub struct Stamper<'a, T: Scalar> {
+    system: &'a mut LinearSystem<T>,
+    layout: &'a Layout,
+    entry: &'a Entry,
+    state: &'a [f64],
+    point: &'a [f64],
+}
+impl<'a, T: Scalar> Stamper<'a, T> {
+    pub(crate) fn new(system: &'a mut LinearSystem<T>, layout: &'a Layout, entry: &'a Entry, state: &'a [f64], point: &'a [f64]) -> Self {
+        Self {
+            system,
+            layout,
+            entry,
+            state,
+            point,
+        }
+    }
+
+    // the unknown holding the voltage of a terminal, or None if it is on a ground net
+    pub fn node(&self, terminal: usize) -> Option<usize> {
+        self.entry.nodes.get(terminal).copied().flatten()
+    }
+
+    // the unknown holding one of the component's branch currents
+    pub fn branch(&self, index: usize) -> Option<usize> {
+        self.entry.branches.get(index).copied()
+    }
+
+    // the unknown holding the current through another component from its node1 to its node2
+    pub fn control_current(&self, name: &str) -> Result<usize, CircuitError> {
+        self.layout
+            .current_unknown(name)
+            .ok_or_else(|| CircuitError::UnknownComponent(name.to_string()))
+    }
+
+    // the state the component reported at the previous time point of a transient simulation
+    pub fn state(&self) -> &[f64] {
+        self.state
+    }
+
+    // the point a nonlinear component is linearized about, as returned by Component::linearize
+    // at the previous Newton iteration (empty at the first one). AC analysis linearizes about
+    // the DC operating point.
+    pub fn operating_point(&self) -> &[f64] {
+        self.point
+    }
+
+    pub fn add(&mut self, row: Option<usize>, col: Option<usize>, value: T) {
+        self.system.add(row, col, value);
+    }
+
+    pub fn add_rhs(&mut self, row: Option<usize>, value: T) {
+        self.system.add_rhs(row, value);
+    }
+
+    pub fn conductance(&mut self, terminal1: usize, terminal2: usize, g: T) {
+        self.system.stamp_conductance(self.node(terminal1), self.node(terminal2), g);
+    }
+
+    // a current injected into terminal `to` and drawn from terminal `from`
+    pub fn current(&mut self, from: usize, to: usize, current: T) {
+        self.system.stamp_current(self.node(from), self.node(to), current);
+    }
+
+    // V(plus) - V(minus) = voltage, with the current from plus to minus as branch `branch`
+    pub fn voltage(&mut self, plus: usize, minus: usize, branch: usize, voltage: T) {
+        let branch = self.branch(branch).expect("component stamped a branch it did not declare");
+        self.system.stamp_voltage(self.node(plus), self.node(minus), branch, voltage);
+    }
+}
+
+// The solved values a component can read back to report its current and state
+pub struct Values<'a, T: Scalar> {
+    solution: &'a [T],
+    layout: &'a Layout,
+    entry: &'a Entry,
+    state: &'a [f64],
+    point: &'a [f64],
+}
+impl<'a, T: Scalar> Values<'a, T> {
+    pub(crate) fn new(solution: &'a [T], layout: &'a Layout, entry: &'a Entry, state: &'a [f64], point: &'a [f64]) -> Self {
+        Self {
+            solution,
+            layout,
+            entry,
+            state,
+            point,
+        }
+    }
+
+    pub fn voltage(&self, terminal: usize) -> T {
+        match self.entry.nodes.get(terminal).copied().flatten() {
+            Some(index) => self.solution[index],
+            None => T::from(0.0),
+        }
+    }
+
+    pub fn branch_current(&self, index: usize) -> T {
+        match self.entry.branches.get(index) {
+            Some(&index) => self.solution[index],
+            None => T::from(0.0),
+        }
+    }
+
+    pub fn control_current(&self, name: &str) -> T {
+        match self.layout.current_unknown(name) {
+            Some(index) => self.solution[index],
+            None => T::from(0.0),
+        }
+    }
+
+    pub fn state(&self) -> &[f64] {
+        self.state
+    }
+
+    pub fn operating_point(&self) -> &[f64] {
+        self.point
+    }
+}
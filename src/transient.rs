@@ -0,0 +1,181 @@
+// src/transient.rs
+
+use std::collections::HashMap;
+
+use crate::error::CircuitError;
+use crate::stamp::Analysis;
+use crate::types::Circuit;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegrationMethod {
+    BackwardEuler,
+    Trapezoidal,
+}
+
+// Time series produced by a transient simulation.
+// node_voltages is indexed by node id and then by time point, and voltages and
+// currents follow the same conventions as the DC solver.
+pub struct TransientResult {
+    pub times: Vec<f64>,
+    pub node_voltages: Vec<Vec<f64>>,
+    pub voltages: HashMap<String, Vec<f64>>,
+    pub currents: HashMap<String, Vec<f64>>,
+}
+impl TransientResult {
+    pub fn node_voltage(&self, node: usize) -> Option<&[f64]> {
+        self.node_voltages.get(node).map(|values| values.as_slice())
+    }
+
+    pub fn voltage(&self, name: &str) -> Option<&[f64]> {
+        self.voltages.get(name).map(|values| values.as_slice())
+    }
+
+    pub fn current(&self, name: &str) -> Option<&[f64]> {
+        self.currents.get(name).map(|values| values.as_slice())
+    }
+}
+
+// Transient analysis.
+// The circuit starts from its DC operating point, with the capacitor initial_voltage and
+// inductor initial_current values forced where they are given, and then every component
+// is stamped with its companion model for each time step, built from the state it reported
+// at the previous time point.
+impl Circuit {
+    pub fn simulate_transient(&self, t_stop: f64, t_step: f64) -> Result<TransientResult, CircuitError> {
+        self.simulate_transient_with(t_stop, t_step, IntegrationMethod::Trapezoidal)
+    }
+
+    pub fn simulate_transient_with(&self, t_stop: f64, t_step: f64, method: IntegrationMethod) -> Result<TransientResult, CircuitError> {
+        if t_step <= 0.0 || !t_step.is_finite() || t_stop <= 0.0 || !t_stop.is_finite() {
+            return Err(CircuitError::InvalidAnalysis("transient times must be positive".to_string()));
+        }
+        let steps = ((t_stop / t_step).round() as usize).max(1);
+
+        let mut result = TransientResult {
+            times: Vec::with_capacity(steps + 1),
+            node_voltages: vec![Vec::with_capacity(steps + 1); self.nodes.len()],
+            voltages: HashMap::new(),
+            currents: HashMap::new(),
+        };
+
+        let mut states: HashMap<String, Vec<f64>> = HashMap::new();
+        let mut points: HashMap<String, Vec<f64>> = HashMap::new();
+        for step in 0..=steps {
+            let time = step as f64 * t_step;
+            let analysis = if step == 0 {
+                Analysis::TransientStart { use_initial_conditions: true }
+            } else {
+                Analysis::TransientStep { time, step: t_step, method }
+            };
+            self.transient_point(&analysis, &mut states, &mut points, &mut result)?;
+        }
+
+        Ok(result)
+    }
+
+    // solve a single time point and record it, nonlinear components start from the points
+    // they converged at in the previous one
+    fn transient_point(
+        &self,
+        analysis: &Analysis,
+        states: &mut HashMap<String, Vec<f64>>,
+        points: &mut HashMap<String, Vec<f64>>,
+        result: &mut TransientResult,
+    ) -> Result<(), CircuitError> {
+        let layout = self.mna_layout(analysis)?;
+        let solution = self.solve_real(&layout, analysis, states, points)?;
+
+        let node_voltages = layout.node_values(&solution);
+        let node_voltage = |node: Option<usize>| node.map(|n| node_voltages[n]).unwrap_or(0.0);
+
+        // record results and update the component states
+        result.times.push(analysis.time());
+        for (node, voltage) in node_voltages.iter().enumerate() {
+            result.node_voltages[node].push(*voltage);
+        }
+        let mut next_states = HashMap::new();
+        for name in &layout.names {
+            let component = self.components[name].as_ref();
+            let base = component.component();
+            let (plus, minus) = component.voltage_terminals();
+            let voltage = node_voltage(base.node(plus)) - node_voltage(base.node(minus));
+            let state = states.get(name).map(|state| state.as_slice()).unwrap_or(&[]);
+            let point = points.get(name).map(|point| point.as_slice()).unwrap_or(&[]);
+            let values = layout.values(name, &solution, state, point);
+            let current = component.current(&values, analysis);
+
+            next_states.insert(name.clone(), component.transient_state(&values, analysis));
+            result.voltages.entry(name.clone()).or_default().push(voltage);
+            if let Some(current) = current {
+                result.currents.entry(name.clone()).or_default().push(current);
+            }
+        }
+        *states = next_states;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spice::parse_spice;
+    use crate::types::{Capacitor, Circuit, Polarity, Resistor, VoltageSource};
+    use crate::waveform::Waveform;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn only_capacitors_with_an_initial_condition_are_forced() {
+        // C1 is across the source, so forcing it to 0 V would contradict V1
+        let circuit = parse_spice("t\nV1 a 0 5\nC1 a 0 1u\nR1 a b 1k\nC2 b 0 1u IC=1\n").unwrap();
+        let result = circuit.simulate_transient(1e-3, 1e-5).unwrap();
+        let b = circuit.net_node("b").unwrap();
+        assert!(close(result.voltage("C1").unwrap()[0], 5.0));
+        assert!(close(result.node_voltage(b).unwrap()[0], 1.0));
+        assert!(close(result.current("R1").unwrap()[0], 4e-3));
+        // C2 charges towards 5 V with a time constant of 1 ms
+        let expected = 5.0 - 4.0 * (-1.0_f64).exp();
+        assert!((result.node_voltage(b).unwrap().last().unwrap() - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn only_inductors_with_an_initial_condition_are_forced() {
+        // L1 is in series with I1, so forcing it to 0 A would contradict the source
+        let circuit = parse_spice("t\nI1 0 a 1m\nL1 a b 1m\nR1 b 0 1k\nL2 c 0 1m IC=2m\nR2 c 0 1k\n").unwrap();
+        let result = circuit.simulate_transient(1e-6, 1e-7).unwrap();
+        assert!(close(result.current("L1").unwrap()[0], 1e-3));
+        assert!(close(result.current("L2").unwrap()[0], 2e-3));
+        // L2 discharges through R2, so the voltage across it is -i * R at the start
+        assert!(close(result.voltage("L2").unwrap()[0], -2.0));
+    }
+
+    #[test]
+    fn pulse_without_delay_or_rise_charges_from_its_initial_value() {
+        let mut source = VoltageSource::new("V1", 0.0, Polarity::Normal);
+        source.waveform = Some(Waveform::Pulse {
+            initial: 0.0,
+            pulsed: 1.0,
+            delay: 0.0,
+            rise: 0.0,
+            fall: 0.0,
+            width: f64::INFINITY,
+            period: None,
+        });
+        let mut circuit = Circuit::new();
+        circuit.add_component(source).unwrap();
+        circuit.add_component(Resistor::new("R1", 1e3)).unwrap();
+        circuit.add_component(Capacitor::new("C1", 1e-6)).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("R1", 1, "in"), ("R1", 2, "out"), ("C1", 1, "out"), ("C1", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+
+        let result = circuit.simulate_transient(1e-3, 1e-5).unwrap();
+        let out = result.node_voltage(circuit.net_node("out").unwrap()).unwrap();
+        assert_eq!(out[0], 0.0);
+        // one time constant in, the capacitor is at about 1 - 1/e
+        let at_tau = out[result.times.iter().position(|t| (t - 1e-3).abs() < 1e-9).unwrap()];
+        assert!((at_tau - (1.0 - (-1.0f64).exp())).abs() < 0.01, "{}", at_tau);
+    }
+}
@@ -1,12 +1,27 @@
 // src/types.rs
 
+use std::any::Any;
 use std::collections::HashMap;
 
+use crate::complex::Complex;
+use crate::error::CircuitError;
+use crate::matrix::Scalar;
+use crate::stamp::{Analysis, Stamper, Values};
+use crate::transient::IntegrationMethod;
+use crate::validation::Coupling;
+use crate::waveform::Waveform;
+
 // Circuits
 pub struct Circuit {
     pub nodes: Vec<Node>,
     pub wires: HashMap<usize, Wire>,
     pub components: HashMap<String, Box<dyn Component>>,
+    next_wire_id: usize,
+}
+impl Default for Circuit {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Circuit {
     pub fn new() -> Self {
@@ -14,28 +29,33 @@ impl Circuit {
             nodes: Vec::new(),
             wires: HashMap::new(),
             components: HashMap::new(),
+            next_wire_id: 0,
         }
     }
 
-    pub fn add_component(&mut self, mut component: impl Component + 'static) {
-        // create new nodes for the component
-        let node1 = self.new_node();
-        let node2 = self.new_node();
+    pub fn add_component(&mut self, mut component: impl Component + 'static) -> Result<(), CircuitError> {
+        // component names must be unique
+        let name = component.component().name.clone();
+        if self.components.contains_key(&name) {
+            return Err(CircuitError::DuplicateComponent(name));
+        }
 
-        // add the nodes to the component
-        component.component_mut().node1 = Some(node1);
-        component.component_mut().node2 = Some(node2);
+        // create a new node for every terminal of the component
+        let nodes: Vec<usize> = component.terminals().iter().map(|_| self.new_node()).collect();
 
-        // get the component's name
-        let name = component.component().name.clone();
+        // add the new connection to the nodes
+        let connection = ConnectionItem::Component(name.clone());
+        for &node in &nodes {
+            self.get_node_mut(node).unwrap().add_connection(connection.clone());
+        }
+
+        // add the nodes to the component
+        component.component_mut().nodes = nodes;
 
         // add the component to the circuit with it's id as the key
-        self.components.insert(name.clone(), Box::new(component));
+        self.components.insert(name, Box::new(component));
 
-        // add the new connection to the nodes
-        let connection = ConnectionItem::Component(name);
-        self.get_node_mut(node1).unwrap().add_connection(connection.clone());
-        self.get_node_mut(node2).unwrap().add_connection(connection);
+        Ok(())
     }
 
     pub fn get_component(&self, name: &str) -> Option<&dyn Component> {
@@ -64,25 +84,137 @@ impl Circuit {
         self.nodes.get_mut(id)
     }
 
-    pub fn connect(&mut self, node1: usize, node2: usize) -> Result<Wire, &str> {
+    // mark a node as a ground (reference) node, all voltages are reported relative to it
+    pub fn set_ground(&mut self, node: usize) -> Result<(), CircuitError> {
+        match self.get_node_mut(node) {
+            Some(node) => {
+                node.ground = true;
+                Ok(())
+            }
+            None => Err(CircuitError::UnknownNode(node)),
+        }
+    }
+
+    pub fn connect(&mut self, node1: usize, node2: usize) -> Result<Wire, CircuitError> {
         // verify that the nodes exist
-        if node1 >= self.nodes.len() || node2 >= self.nodes.len() {
-            return Err("Node does not exist");
+        for node in [node1, node2] {
+            if node >= self.nodes.len() {
+                return Err(CircuitError::UnknownNode(node));
+            }
         }
         if node1 == node2 {
-            return Err("Cannot connect a node to itself");
+            return Err(CircuitError::SelfConnection(node1));
         }
 
         // create a new wire
-        let wire_id = self.wires.len();
+        let wire_id = self.next_wire_id;
+        self.next_wire_id += 1;
         let wire = Wire::new(wire_id, node1, node2);
+        self.wires.insert(wire_id, wire.clone());
 
         // add the new connection to the nodes
         let connection = ConnectionItem::Wire(wire_id);
         self.get_node_mut(node1).unwrap().add_connection(connection.clone());
         self.get_node_mut(node2).unwrap().add_connection(connection);
 
-        return Ok(wire);
+        Ok(wire)
+    }
+
+    // remove a component along with its connections, leaving its nodes in place
+    pub fn remove_component(&mut self, name: &str) -> Result<Box<dyn Component>, CircuitError> {
+        let component = self
+            .components
+            .remove(name)
+            .ok_or_else(|| CircuitError::UnknownComponent(name.to_string()))?;
+
+        for &node in &component.component().nodes {
+            self.nodes[node]
+                .connected
+                .retain(|connection| !matches!(connection, ConnectionItem::Component(other) if other == name));
+        }
+
+        Ok(component)
+    }
+
+    // remove a wire, the nodes it joined are no longer connected through it
+    pub fn disconnect(&mut self, wire_id: usize) -> Result<Wire, CircuitError> {
+        let wire = self.wires.remove(&wire_id).ok_or(CircuitError::UnknownWire(wire_id))?;
+
+        for node in [wire.node1, wire.node2] {
+            self.nodes[node]
+                .connected
+                .retain(|connection| !matches!(connection, ConnectionItem::Wire(id) if *id == wire_id));
+        }
+
+        Ok(wire)
+    }
+
+    // Drop every node with nothing connected to it and renumber the rest in order, updating
+    // the wires and components that refer to them. Returns the new id of every old node id,
+    // None for the nodes that were dropped.
+    pub fn compact(&mut self) -> Vec<Option<usize>> {
+        let mut remap = vec![None; self.nodes.len()];
+        let mut nodes = Vec::new();
+        for mut node in std::mem::take(&mut self.nodes) {
+            if node.connected.is_empty() {
+                continue;
+            }
+            remap[node.id] = Some(nodes.len());
+            node.id = nodes.len();
+            nodes.push(node);
+        }
+        self.nodes = nodes;
+
+        // anything still referring to a node keeps it connected, so it was not dropped
+        let new_id = |node: usize| remap[node].expect("connected node was dropped");
+        for wire in self.wires.values_mut() {
+            wire.node1 = new_id(wire.node1);
+            wire.node2 = new_id(wire.node2);
+        }
+        for component in self.components.values_mut() {
+            for node in component.component_mut().nodes.iter_mut() {
+                *node = new_id(*node);
+            }
+        }
+
+        remap
+    }
+
+    // add a component whose nodes are already set, keeping their ids (used when loading a circuit)
+    pub(crate) fn insert_component(&mut self, component: Box<dyn Component>) -> Result<(), CircuitError> {
+        let name = component.component().name.clone();
+        if self.components.contains_key(&name) {
+            return Err(CircuitError::DuplicateComponent(name));
+        }
+        let nodes = &component.component().nodes;
+        if let Some(&node) = nodes.iter().find(|&&node| node >= self.nodes.len()) {
+            return Err(CircuitError::UnknownNode(node));
+        }
+
+        let connection = ConnectionItem::Component(name.clone());
+        for &node in nodes {
+            self.nodes[node].add_connection(connection.clone());
+        }
+        self.components.insert(name, component);
+        Ok(())
+    }
+
+    // add a wire keeping its id (used when loading a circuit)
+    pub(crate) fn insert_wire(&mut self, wire: Wire) -> Result<(), CircuitError> {
+        for node in [wire.node1, wire.node2] {
+            if node >= self.nodes.len() {
+                return Err(CircuitError::UnknownNode(node));
+            }
+        }
+        if wire.node1 == wire.node2 {
+            return Err(CircuitError::SelfConnection(wire.node1));
+        }
+
+        self.next_wire_id = self.next_wire_id.max(wire.id + 1);
+        self.nodes[wire.node1].add_connection(ConnectionItem::Wire(wire.id));
+        self.nodes[wire.node2].add_connection(ConnectionItem::Wire(wire.id));
+        self.wires.insert(wire.id, wire);
+        Ok(())
     }
 
     fn new_node(&mut self) -> usize {
@@ -103,13 +235,17 @@ pub struct Node {
     pub id: usize,
     pub voltage: Option<f64>,
     pub connected: Vec<ConnectionItem>,
+    pub ground: bool,
+    pub label: Option<String>,
 }
 impl Node {
     pub fn new(id: usize) -> Self {
         Self {
-            id: id,
+            id,
             voltage: None,
             connected: Vec::new(),
+            ground: false,
+            label: None,
         }
     }
 
@@ -124,6 +260,7 @@ pub enum ConnectionItem {
     Component(String),
 }
 
+#[derive(Clone)]
 pub struct Wire {
     pub node1: usize,
     pub node2: usize,
@@ -132,9 +269,9 @@ pub struct Wire {
 impl Wire {
     pub fn new(id: usize, node1: usize, node2: usize) -> Self {
         Self {
-            node1: node1,
-            node2: node2,
-            id: id,
+            node1,
+            node2,
+            id,
         }
     }
 }
@@ -143,11 +280,81 @@ impl Wire {
 pub trait Component {
     fn component(&self) -> &BaseComponent;
     fn component_mut(&mut self) -> &mut BaseComponent;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    // names of the component's terminals, a node is created for each one when it is added to a circuit
+    fn terminals(&self) -> &'static [&'static str] {
+        &["node1", "node2"]
+    }
+
+    // the terminals the component voltage is measured across, V(first) - V(second)
+    fn voltage_terminals(&self) -> (usize, usize) {
+        (0, 1)
+    }
+
+    fn terminal_node(&self, terminal: &str) -> Option<usize> {
+        let index = self.terminals().iter().position(|name| *name == terminal)?;
+        self.component().node(index)
+    }
+
+    // Stamping
+    // A component describes itself to the solvers by adding its contribution to the MNA
+    // system through a Stamper. If it asks for any branch currents, branch 0 must be the
+    // current through the component from node1 to node2.
+    fn branch_count(&self, _analysis: &Analysis) -> usize {
+        0
+    }
+
+    // stamp for DC and transient analysis
+    fn stamp(&self, stamper: &mut Stamper<f64>, analysis: &Analysis) -> Result<(), CircuitError>;
+
+    fn stamp_ac(&self, _stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        Err(CircuitError::InvalidAnalysis(format!("{} does not support AC analysis", self.component().name)))
+    }
+
+    // the current through the component from node1 to node2. The default reads the first
+    // branch current, so a component without branches must compute its own or report None.
+    fn current(&self, values: &Values<f64>, analysis: &Analysis) -> Option<f64> {
+        (self.branch_count(analysis) > 0).then(|| values.branch_current(0))
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, omega: f64) -> Option<Complex> {
+        (self.branch_count(&Analysis::Ac { omega }) > 0).then(|| values.branch_current(0))
+    }
+
+    // state to keep until the next time point of a transient simulation
+    fn transient_state(&self, _values: &Values<f64>, _analysis: &Analysis) -> Vec<f64> {
+        Vec::new()
+    }
+
+    // the name of the component whose current controls this one
+    fn controlling_current(&self) -> Option<&str> {
+        None
+    }
+
+    // Nonlinear components are stamped as their linearization about Stamper::operating_point
+    // and solved by Newton-Raphson iteration
+    fn is_nonlinear(&self) -> bool {
+        false
+    }
+
+    // the point to linearize about at the next Newton iteration, from the latest solution and
+    // the point used for the last one (Values::operating_point)
+    fn linearize(&self, _values: &Values<f64>) -> Vec<f64> {
+        Vec::new()
+    }
+
+    // Topology
+    // the pairs of terminals current can pass between and how, which Circuit::validate
+    // checks for paths to ground. By default every terminal conducts to the next.
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> {
+        (1..self.terminals().len()).map(|terminal| (terminal - 1, terminal, Coupling::Conductive)).collect()
+    }
 }
 
 pub struct BaseComponent {
-    pub node1: Option<usize>,
-    pub node2: Option<usize>,
+    pub nodes: Vec<usize>, // one node per terminal, in the order given by Component::terminals
 
     pub name: String,
     
@@ -155,6 +362,20 @@ pub struct BaseComponent {
     pub voltage: Option<f64>,
 }
 
+impl BaseComponent {
+    pub fn node(&self, index: usize) -> Option<usize> {
+        self.nodes.get(index).copied()
+    }
+
+    pub fn node1(&self) -> Option<usize> {
+        self.node(0)
+    }
+
+    pub fn node2(&self) -> Option<usize> {
+        self.node(1)
+    }
+}
+
 pub struct Resistor {
     pub component: BaseComponent,
     pub resistance: f64,
@@ -162,41 +383,139 @@ pub struct Resistor {
 impl Component for Resistor {
     fn component(&self) -> &BaseComponent { &self.component }
     fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.stamp_resistance(stamper)
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        self.stamp_resistance(stamper)
+    }
+
+    fn current(&self, values: &Values<f64>, _analysis: &Analysis) -> Option<f64> {
+        Some((values.voltage(0) - values.voltage(1)) / self.resistance)
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, _omega: f64) -> Option<Complex> {
+        Some((values.voltage(0) - values.voltage(1)) / Complex::from(self.resistance))
+    }
 }
 impl Resistor {
     pub fn new(name: &str, resistance: f64) -> Self {
         Self {
             component: BaseComponent {
-                node1: None,
-                node2: None,
+                nodes: Vec::new(),
                 name: name.to_string(),
                 current: None,
                 voltage: None,
             },
-            resistance: resistance,
+            resistance,
+        }
+    }
+
+    fn stamp_resistance<T: Scalar>(&self, stamper: &mut Stamper<T>) -> Result<(), CircuitError> {
+        if self.resistance <= 0.0 {
+            return Err(CircuitError::InvalidValue {
+                component: self.component.name.clone(),
+                reason: "resistance must be positive".to_string(),
+            });
         }
+        stamper.conductance(0, 1, T::from(1.0 / self.resistance));
+        Ok(())
     }
 }
 
 pub struct Capacitor {
     pub component: BaseComponent,
     pub capacitance: f64,
+    pub initial_voltage: Option<f64>, // V(node1) - V(node2) at the start of a transient simulation
 }
 impl Component for Capacitor {
     fn component(&self) -> &BaseComponent { &self.component }
     fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::Capacitive)] }
+    fn branch_count(&self, analysis: &Analysis) -> usize {
+        // the initial voltage is forced through a branch
+        usize::from(self.initial_condition(analysis).is_some())
+    }
+
+    fn stamp(&self, stamper: &mut Stamper<f64>, analysis: &Analysis) -> Result<(), CircuitError> {
+        if let Some(voltage) = self.initial_condition(analysis) {
+            stamper.voltage(0, 1, 0, voltage);
+            return Ok(());
+        }
+        // a capacitor is an open circuit at DC, and a norton companion model in a time step
+        if let Analysis::TransientStep { step, method, .. } = *analysis {
+            let (g, i_eq) = self.companion(step, method, stamper.state());
+            stamper.conductance(0, 1, g);
+            stamper.current(1, 0, i_eq);
+        }
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, omega: f64) -> Result<(), CircuitError> {
+        stamper.conductance(0, 1, Complex::new(0.0, omega * self.capacitance));
+        Ok(())
+    }
+
+    fn current(&self, values: &Values<f64>, analysis: &Analysis) -> Option<f64> {
+        if self.initial_condition(analysis).is_some() {
+            return Some(values.branch_current(0));
+        }
+        match *analysis {
+            Analysis::TransientStep { step, method, .. } => {
+                let (g, i_eq) = self.companion(step, method, values.state());
+                Some(g * (values.voltage(0) - values.voltage(1)) - i_eq)
+            }
+            _ => Some(0.0),
+        }
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, omega: f64) -> Option<Complex> {
+        Some((values.voltage(0) - values.voltage(1)) * Complex::new(0.0, omega * self.capacitance))
+    }
+
+    fn transient_state(&self, values: &Values<f64>, analysis: &Analysis) -> Vec<f64> {
+        vec![values.voltage(0) - values.voltage(1), self.current(values, analysis).unwrap_or_default()]
+    }
 }
 impl Capacitor {
     pub fn new(name: &str, capacitance: f64) -> Self {
         Self {
             component: BaseComponent {
-                node1: None,
-                node2: None,
+                nodes: Vec::new(),
                 name: name.to_string(),
                 current: None,
                 voltage: None,
             },
-            capacitance: capacitance,
+            capacitance,
+            initial_voltage: None,
+        }
+    }
+
+    // the voltage forced at the start of a transient simulation, if one was given
+    fn initial_condition(&self, analysis: &Analysis) -> Option<f64> {
+        match analysis {
+            Analysis::TransientStart { use_initial_conditions: true } => self.initial_voltage,
+            _ => None,
+        }
+    }
+
+    // Norton companion for a time step, so that i = g * v - i_eq, from the previous [voltage, current]
+    fn companion(&self, step: f64, method: IntegrationMethod, state: &[f64]) -> (f64, f64) {
+        let (voltage, current) = (state.first().copied().unwrap_or(0.0), state.get(1).copied().unwrap_or(0.0));
+        match method {
+            IntegrationMethod::BackwardEuler => {
+                let g = self.capacitance / step;
+                (g, g * voltage)
+            }
+            IntegrationMethod::Trapezoidal => {
+                let g = 2.0 * self.capacitance / step;
+                (g, g * voltage + current)
+            }
         }
     }
 }
@@ -204,22 +523,86 @@ impl Capacitor {
 pub struct Inductor {
     pub component: BaseComponent,
     pub inductance: f64,
+    pub initial_current: Option<f64>, // current from node1 to node2 at the start of a transient simulation
 }
 impl Component for Inductor {
     fn component(&self) -> &BaseComponent { &self.component }
     fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::Inductive)] }
+    fn branch_count(&self, _analysis: &Analysis) -> usize {
+        1
+    }
+
+    fn stamp(&self, stamper: &mut Stamper<f64>, analysis: &Analysis) -> Result<(), CircuitError> {
+        if let Some(current) = self.initial_condition(analysis) {
+            // the branch current is forced, so the branch row becomes I = i0
+            let branch = stamper.branch(0);
+            stamper.add(stamper.node(0), branch, 1.0);
+            stamper.add(stamper.node(1), branch, -1.0);
+            stamper.add(branch, branch, 1.0);
+            stamper.add_rhs(branch, current);
+            return Ok(());
+        }
+        match *analysis {
+            Analysis::TransientStep { step, method, .. } => {
+                // thevenin companion model, V(node1) - V(node2) - r * I = v_eq
+                let (r, v_eq) = self.companion(step, method, stamper.state());
+                stamper.voltage(0, 1, 0, v_eq);
+                stamper.add(stamper.branch(0), stamper.branch(0), -r);
+            }
+            // an inductor is a short circuit at DC
+            _ => stamper.voltage(0, 1, 0, 0.0),
+        }
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, omega: f64) -> Result<(), CircuitError> {
+        // V(node1) - V(node2) - jwL * I = 0, which stays well defined at 0 Hz
+        stamper.voltage(0, 1, 0, Complex::default());
+        stamper.add(stamper.branch(0), stamper.branch(0), Complex::new(0.0, -omega * self.inductance));
+        Ok(())
+    }
+
+    fn transient_state(&self, values: &Values<f64>, _analysis: &Analysis) -> Vec<f64> {
+        vec![values.voltage(0) - values.voltage(1), values.branch_current(0)]
+    }
 }
 impl Inductor {
     pub fn new(name: &str, inductance: f64) -> Self {
         Self {
             component: BaseComponent {
-                node1: None,
-                node2: None,
+                nodes: Vec::new(),
                 name: name.to_string(),
                 current: None,
                 voltage: None,
             },
-            inductance: inductance,
+            inductance,
+            initial_current: None,
+        }
+    }
+
+    // the current forced at the start of a transient simulation, if one was given
+    fn initial_condition(&self, analysis: &Analysis) -> Option<f64> {
+        match analysis {
+            Analysis::TransientStart { use_initial_conditions: true } => self.initial_current,
+            _ => None,
+        }
+    }
+
+    // Thevenin companion for a time step, so that v = r * i + v_eq, from the previous [voltage, current]
+    fn companion(&self, step: f64, method: IntegrationMethod, state: &[f64]) -> (f64, f64) {
+        let (voltage, current) = (state.first().copied().unwrap_or(0.0), state.get(1).copied().unwrap_or(0.0));
+        match method {
+            IntegrationMethod::BackwardEuler => {
+                let r = self.inductance / step;
+                (r, -r * current)
+            }
+            IntegrationMethod::Trapezoidal => {
+                let r = 2.0 * self.inductance / step;
+                (r, -r * current - voltage)
+            }
         }
     }
 }
@@ -228,37 +611,75 @@ pub struct VoltageSource {
     pub component: BaseComponent,
     pub voltage: f64,
     pub polarity: Polarity, // if normal, node1 should be plus and node2 should be minus
+    pub ac_magnitude: f64,
+    pub ac_phase: f64, // degrees
+    pub waveform: Option<Waveform>, // replaces the voltage during transient analysis
 }
 impl Component for VoltageSource {
     fn component(&self) -> &BaseComponent { &self.component }
     fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::VoltageSource)] }
+    fn branch_count(&self, _analysis: &Analysis) -> usize {
+        1
+    }
+
+    fn stamp(&self, stamper: &mut Stamper<f64>, analysis: &Analysis) -> Result<(), CircuitError> {
+        stamper.voltage(0, 1, 0, self.polarity.sign() * self.value(analysis));
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        let phasor = Complex::from_polar(self.ac_magnitude, self.ac_phase.to_radians());
+        stamper.voltage(0, 1, 0, Complex::from(self.polarity.sign()) * phasor);
+        Ok(())
+    }
 }
 impl VoltageSource {
     pub fn new(name: &str, voltage: f64, polarity: Polarity) -> Self {
         Self {
             component: BaseComponent {
-                node1: None,
-                node2: None,
+                nodes: Vec::new(),
                 name: name.to_string(),
                 current: None,
                 voltage: None,
             },
-            voltage: voltage,
-            polarity: polarity,
+            voltage,
+            polarity,
+            ac_magnitude: 0.0,
+            ac_phase: 0.0,
+            waveform: None,
+        }
+    }
+
+    // the voltage at a time point of a transient simulation
+    pub fn voltage_at(&self, time: f64) -> f64 {
+        match &self.waveform {
+            Some(waveform) => waveform.value(time),
+            None => self.voltage,
+        }
+    }
+
+    fn value(&self, analysis: &Analysis) -> f64 {
+        match (analysis, &self.waveform) {
+            (Analysis::TransientStart { .. }, Some(waveform)) => waveform.start_value(),
+            _ if analysis.is_transient() => self.voltage_at(analysis.time()),
+            _ => self.voltage,
         }
     }
 
     pub fn positive_node(&self) -> Option<usize> {
         match self.polarity {
-            Polarity::Normal => self.component.node1,
-            Polarity::Inverted => self.component.node2,
+            Polarity::Normal => self.component.node1(),
+            Polarity::Inverted => self.component.node2(),
         }
     }
 
     pub fn negative_node(&self) -> Option<usize> {
         match self.polarity {
-            Polarity::Normal => self.component.node2,
-            Polarity::Inverted => self.component.node1
+            Polarity::Normal => self.component.node2(),
+            Polarity::Inverted => self.component.node1()
         }
     }
 }
@@ -267,42 +688,1451 @@ pub struct CurrentSource {
     pub component: BaseComponent,
     pub current: f64,
     pub polarity: Polarity, // if normal, current will flow from node2 to node1
+    pub ac_magnitude: f64,
+    pub ac_phase: f64, // degrees
+    pub waveform: Option<Waveform>, // replaces the current during transient analysis
 }
 impl Component for CurrentSource {
     fn component(&self) -> &BaseComponent { &self.component }
     fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::CurrentSource)] }
+    fn stamp(&self, stamper: &mut Stamper<f64>, analysis: &Analysis) -> Result<(), CircuitError> {
+        // the current flows out of node1 when the polarity is normal
+        stamper.current(1, 0, self.polarity.sign() * self.value(analysis));
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        let phasor = Complex::from_polar(self.ac_magnitude, self.ac_phase.to_radians());
+        stamper.current(1, 0, Complex::from(self.polarity.sign()) * phasor);
+        Ok(())
+    }
+
+    fn current(&self, _values: &Values<f64>, analysis: &Analysis) -> Option<f64> {
+        Some(-self.polarity.sign() * self.value(analysis))
+    }
+
+    fn ac_current(&self, _values: &Values<Complex>, _omega: f64) -> Option<Complex> {
+        Some(-(Complex::from(self.polarity.sign()) * Complex::from_polar(self.ac_magnitude, self.ac_phase.to_radians())))
+    }
 }
 impl CurrentSource {
     pub fn new(name: &str, current: f64, polarity: Polarity) -> Self {
         Self {
             component: BaseComponent {
-                node1: None,
-                node2: None,
+                nodes: Vec::new(),
                 name: name.to_string(),
                 current: None,
                 voltage: None,
             },
-            current: current,
-            polarity: polarity,
+            current,
+            polarity,
+            ac_magnitude: 0.0,
+            ac_phase: 0.0,
+            waveform: None,
         }
     }
 
     pub fn input_node(&self) -> Option<usize> {
         match self.polarity {
-            Polarity::Normal => self.component.node2,
-            Polarity::Inverted => self.component.node1,
+            Polarity::Normal => self.component.node2(),
+            Polarity::Inverted => self.component.node1(),
+        }
+    }
+
+    // the current at a time point of a transient simulation
+    pub fn current_at(&self, time: f64) -> f64 {
+        match &self.waveform {
+            Some(waveform) => waveform.value(time),
+            None => self.current,
+        }
+    }
+
+    fn value(&self, analysis: &Analysis) -> f64 {
+        match (analysis, &self.waveform) {
+            (Analysis::TransientStart { .. }, Some(waveform)) => waveform.start_value(),
+            _ if analysis.is_transient() => self.current_at(analysis.time()),
+            _ => self.current,
         }
     }
 
     pub fn output_node(&self) -> Option<usize> {
         match self.polarity {
-            Polarity::Normal => self.component.node1,
-            Polarity::Inverted => self.component.node2
+            Polarity::Normal => self.component.node1(),
+            Polarity::Inverted => self.component.node2()
         }
     }
 }
 
-pub enum Polarity {
-    Normal,
-    Inverted,
-}
\ No newline at end of file
+// Dependent Sources
+// The output of every dependent source is between node1 and node2. Voltage outputs
+// make node1 the positive terminal and current outputs push current out of node1,
+// matching a VoltageSource or CurrentSource with Polarity::Normal. Voltage controlled
+// sources have two more terminals and are controlled by V(control_positive) -
+// V(control_negative), and current controlled sources are controlled by the current
+// through the named component from its node1 to its node2.
+
+// voltage controlled voltage source: V = gain * Vc
+pub struct Vcvs {
+    pub component: BaseComponent,
+    pub gain: f64,
+}
+impl Component for Vcvs {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn terminals(&self) -> &'static [&'static str] { &["node1", "node2", "control_positive", "control_negative"] }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::VoltageSource)] }
+    fn branch_count(&self, _analysis: &Analysis) -> usize {
+        1
+    }
+
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+}
+impl Vcvs {
+    pub fn new(name: &str, gain: f64) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            gain,
+        }
+    }
+
+    // V(node1) - V(node2) - gain * Vc = 0
+    fn stamp_source<T: Scalar>(&self, stamper: &mut Stamper<T>) -> Result<(), CircuitError> {
+        let branch = stamper.branch(0);
+        stamper.voltage(0, 1, 0, T::from(0.0));
+        stamper.add(branch, stamper.node(2), T::from(-self.gain));
+        stamper.add(branch, stamper.node(3), T::from(self.gain));
+        Ok(())
+    }
+}
+
+// voltage controlled current source: I = transconductance * Vc
+pub struct Vccs {
+    pub component: BaseComponent,
+    pub transconductance: f64,
+}
+impl Component for Vccs {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn terminals(&self) -> &'static [&'static str] { &["node1", "node2", "control_positive", "control_negative"] }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::CurrentSource)] }
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+
+    fn current(&self, values: &Values<f64>, _analysis: &Analysis) -> Option<f64> {
+        Some(-self.transconductance * (values.voltage(2) - values.voltage(3)))
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, _omega: f64) -> Option<Complex> {
+        Some(-(Complex::from(self.transconductance) * (values.voltage(2) - values.voltage(3))))
+    }
+}
+impl Vccs {
+    pub fn new(name: &str, transconductance: f64) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            transconductance,
+        }
+    }
+
+    // transconductance * Vc flows out of node1
+    fn stamp_source<T: Scalar>(&self, stamper: &mut Stamper<T>) -> Result<(), CircuitError> {
+        let gm = T::from(self.transconductance);
+        let (n1, n2) = (stamper.node(0), stamper.node(1));
+        let (cp, cm) = (stamper.node(2), stamper.node(3));
+        stamper.add(n1, cp, -gm);
+        stamper.add(n1, cm, gm);
+        stamper.add(n2, cp, gm);
+        stamper.add(n2, cm, -gm);
+        Ok(())
+    }
+}
+
+// current controlled voltage source: V = transresistance * Ic
+pub struct Ccvs {
+    pub component: BaseComponent,
+    pub transresistance: f64,
+    pub control: String,
+}
+impl Component for Ccvs {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::VoltageSource)] }
+    fn branch_count(&self, _analysis: &Analysis) -> usize {
+        1
+    }
+
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+
+    fn controlling_current(&self) -> Option<&str> {
+        Some(&self.control)
+    }
+}
+impl Ccvs {
+    pub fn new(name: &str, transresistance: f64, control: &str) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            transresistance,
+            control: control.to_string(),
+        }
+    }
+
+    // V(node1) - V(node2) - transresistance * Ic = 0
+    fn stamp_source<T: Scalar>(&self, stamper: &mut Stamper<T>) -> Result<(), CircuitError> {
+        let control = Some(stamper.control_current(&self.control)?);
+        let branch = stamper.branch(0);
+        stamper.voltage(0, 1, 0, T::from(0.0));
+        stamper.add(branch, control, T::from(-self.transresistance));
+        Ok(())
+    }
+}
+
+// current controlled current source: I = gain * Ic
+pub struct Cccs {
+    pub component: BaseComponent,
+    pub gain: f64,
+    pub control: String,
+}
+impl Component for Cccs {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::CurrentSource)] }
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        self.stamp_source(stamper)
+    }
+
+    fn current(&self, values: &Values<f64>, _analysis: &Analysis) -> Option<f64> {
+        Some(-self.gain * values.control_current(&self.control))
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, _omega: f64) -> Option<Complex> {
+        Some(-(Complex::from(self.gain) * values.control_current(&self.control)))
+    }
+
+    fn controlling_current(&self) -> Option<&str> {
+        Some(&self.control)
+    }
+}
+impl Cccs {
+    pub fn new(name: &str, gain: f64, control: &str) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            gain,
+            control: control.to_string(),
+        }
+    }
+
+    // gain * Ic flows out of node1
+    fn stamp_source<T: Scalar>(&self, stamper: &mut Stamper<T>) -> Result<(), CircuitError> {
+        let control = Some(stamper.control_current(&self.control)?);
+        let gain = T::from(self.gain);
+        stamper.add(stamper.node(0), control, -gain);
+        stamper.add(stamper.node(1), control, gain);
+        Ok(())
+    }
+}
+
+// fraction of an op amp's output range over which it bends into saturation at each rail
+const RAIL_KNEE: f64 = 0.02;
+
+// Operational amplifier. The output is driven between the output and reference terminals from
+// the voltage between the non-inverting and inverting inputs. With no gain it is ideal (a nullor),
+// which holds both inputs at the same voltage without drawing input current. With a gain it is
+// a voltage controlled voltage source with the given input and output resistance, whose gain
+// rolls off past gain_bandwidth / gain in AC analysis and whose output saturates smoothly
+// between the rails (relative to the reference terminal) in DC and transient analysis.
+pub struct OpAmp {
+    pub component: BaseComponent,
+    pub gain: Option<f64>,
+    pub input_resistance: Option<f64>, // ohms, None for no input current
+    pub output_resistance: f64, // ohms
+    pub gain_bandwidth: Option<f64>, // hertz, None for a gain that doesn't depend on frequency
+    pub rails: Option<(f64, f64)>, // (negative, positive) output limits, volts
+}
+impl Component for OpAmp {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn terminals(&self) -> &'static [&'static str] { &["output", "reference", "non_inverting", "inverting"] }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> {
+        let output = if self.output_resistance > 0.0 { Coupling::Conductive } else { Coupling::VoltageSource };
+        let mut couplings = vec![(0, 1, output)];
+        if self.input_resistance.is_some() {
+            couplings.push((2, 3, Coupling::Conductive));
+        }
+        couplings
+    }
+    fn branch_count(&self, _analysis: &Analysis) -> usize {
+        1
+    }
+
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.validate()?;
+        let Some(gain) = self.gain else {
+            self.stamp_nullor(stamper);
+            return Ok(());
+        };
+        // the output is linearized as slope * Vd + offset
+        let vd = stamper.operating_point().first().copied().unwrap_or(0.0);
+        let (output, slope) = self.output(gain, vd);
+        self.stamp_finite(stamper, slope, output - slope * vd);
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, omega: f64) -> Result<(), CircuitError> {
+        self.validate()?;
+        let Some(gain) = self.gain else {
+            self.stamp_nullor(stamper);
+            return Ok(());
+        };
+        // small signal gain at the DC operating point, with a single pole at gain_bandwidth / gain
+        let vd = stamper.operating_point().first().copied().unwrap_or(0.0);
+        let mut slope = Complex::from(self.output(gain, vd).1);
+        if let Some(gbw) = self.gain_bandwidth {
+            let pole = 2.0 * std::f64::consts::PI * gbw / gain;
+            slope = slope / Complex::new(1.0, omega / pole);
+        }
+        self.stamp_finite(stamper, slope, Complex::default());
+        Ok(())
+    }
+
+    fn is_nonlinear(&self) -> bool {
+        self.gain.is_some() && self.rails.is_some()
+    }
+
+    fn linearize(&self, values: &Values<f64>) -> Vec<f64> {
+        vec![values.voltage(2) - values.voltage(3)]
+    }
+}
+impl OpAmp {
+    pub fn ideal(name: &str) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            gain: None,
+            input_resistance: None,
+            output_resistance: 0.0,
+            gain_bandwidth: None,
+            rails: None,
+        }
+    }
+
+    pub fn new(name: &str, gain: f64) -> Self {
+        Self {
+            gain: Some(gain),
+            ..Self::ideal(name)
+        }
+    }
+
+    fn validate(&self) -> Result<(), CircuitError> {
+        let reason = if self.gain.is_some_and(|gain| gain <= 0.0) {
+            "gain must be positive"
+        } else if self.input_resistance.is_some_and(|r| r <= 0.0) {
+            "input resistance must be positive"
+        } else if self.output_resistance < 0.0 {
+            "output resistance can't be negative"
+        } else if self.gain_bandwidth.is_some_and(|gbw| gbw <= 0.0) {
+            "gain bandwidth product must be positive"
+        } else if self.rails.is_some_and(|(negative, positive)| negative >= positive) {
+            "the negative rail must be below the positive rail"
+        } else {
+            return Ok(());
+        };
+        Err(CircuitError::InvalidValue {
+            component: self.component.name.clone(),
+            reason: reason.to_string(),
+        })
+    }
+
+    // the output voltage and its derivative for an input voltage, which is linear until it comes
+    // within a knee of either rail and then approaches the rail exponentially
+    fn output(&self, gain: f64, vd: f64) -> (f64, f64) {
+        let linear = gain * vd;
+        let Some((negative, positive)) = self.rails else {
+            return (linear, gain);
+        };
+        let knee = RAIL_KNEE * (positive - negative);
+        if linear > positive - knee {
+            let decay = (-(linear - (positive - knee)) / knee).exp();
+            (positive - knee * decay, gain * decay)
+        } else if linear < negative + knee {
+            let decay = ((linear - (negative + knee)) / knee).exp();
+            (negative + knee * decay, gain * decay)
+        } else {
+            (linear, gain)
+        }
+    }
+
+    // the output current is whatever holds V(non_inverting) = V(inverting)
+    fn stamp_nullor<T: Scalar>(&self, stamper: &mut Stamper<T>) {
+        let branch = stamper.branch(0);
+        stamper.add(stamper.node(0), branch, T::from(1.0));
+        stamper.add(stamper.node(1), branch, T::from(-1.0));
+        stamper.add(branch, stamper.node(2), T::from(1.0));
+        stamper.add(branch, stamper.node(3), T::from(-1.0));
+    }
+
+    // V(output) - V(reference) = gain * Vd + offset + output_resistance * I
+    fn stamp_finite<T: Scalar>(&self, stamper: &mut Stamper<T>, gain: T, offset: T) {
+        let branch = stamper.branch(0);
+        stamper.voltage(0, 1, 0, offset);
+        stamper.add(branch, stamper.node(2), -gain);
+        stamper.add(branch, stamper.node(3), gain);
+        stamper.add(branch, branch, T::from(-self.output_resistance));
+        if let Some(resistance) = self.input_resistance {
+            stamper.conductance(2, 3, T::from(1.0 / resistance));
+        }
+    }
+}
+
+// thermal voltage kT/q at 300 K
+const THERMAL_VOLTAGE: f64 = 0.025852;
+// conductance across every junction, which keeps reverse biased junctions from leaving a node floating
+const GMIN: f64 = 1e-12;
+// reverse current of a diode at its breakdown voltage (the SPICE default IBV)
+const BREAKDOWN_CURRENT: f64 = 1e-3;
+
+// Diode following the Shockley equation, conducting from its anode (node1) to its cathode (node2)
+pub struct Diode {
+    pub component: BaseComponent,
+    pub saturation_current: f64, // Is, amps
+    pub emission_coefficient: f64, // n
+    pub series_resistance: f64, // ohms
+    pub breakdown_voltage: Option<f64>, // reverse voltage at which the diode starts conducting, None if it never breaks down
+}
+impl Component for Diode {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn terminals(&self) -> &'static [&'static str] { &["anode", "cathode"] }
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.validate()?;
+        // current from anode to cathode of geq * V + ieq
+        let (geq, ieq) = self.companion(junction_point(stamper.operating_point()));
+        stamper.conductance(0, 1, geq);
+        stamper.current(0, 1, ieq);
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        // small signal conductance at the DC operating point
+        self.validate()?;
+        let (geq, _) = self.companion(junction_point(stamper.operating_point()));
+        stamper.conductance(0, 1, Complex::from(geq));
+        Ok(())
+    }
+
+    fn current(&self, values: &Values<f64>, _analysis: &Analysis) -> Option<f64> {
+        Some(self.junction_current(self.junction_voltage(values.voltage(0) - values.voltage(1))))
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, _omega: f64) -> Option<Complex> {
+        let (geq, _) = self.companion(junction_point(values.operating_point()));
+        Some((values.voltage(0) - values.voltage(1)) * Complex::from(geq))
+    }
+
+    fn is_nonlinear(&self) -> bool {
+        true
+    }
+
+    fn linearize(&self, values: &Values<f64>) -> Vec<f64> {
+        let target = self.junction_voltage(values.voltage(0) - values.voltage(1));
+        vec![self.limit(target, junction_point(values.operating_point()))]
+    }
+}
+impl Diode {
+    pub fn new(name: &str) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            saturation_current: 1e-14,
+            emission_coefficient: 1.0,
+            series_resistance: 0.0,
+            breakdown_voltage: None,
+        }
+    }
+
+    fn validate(&self) -> Result<(), CircuitError> {
+        let reason = if self.saturation_current <= 0.0 {
+            "saturation current must be positive"
+        } else if self.emission_coefficient <= 0.0 {
+            "emission coefficient must be positive"
+        } else if self.series_resistance < 0.0 {
+            "series resistance can't be negative"
+        } else if self.breakdown_voltage.is_some_and(|bv| bv <= 0.0) {
+            "breakdown voltage must be positive"
+        } else {
+            return Ok(());
+        };
+        Err(CircuitError::InvalidValue {
+            component: self.component.name.clone(),
+            reason: reason.to_string(),
+        })
+    }
+
+    fn thermal_voltage(&self) -> f64 {
+        self.emission_coefficient * THERMAL_VOLTAGE
+    }
+
+    // current through the junction at a junction voltage, with a reverse breakdown exponential
+    // that carries BREAKDOWN_CURRENT at the breakdown voltage
+    fn junction_current(&self, vd: f64) -> f64 {
+        let vt = self.thermal_voltage();
+        let mut current = self.saturation_current * ((vd / vt).exp() - 1.0) + GMIN * vd;
+        if let Some(bv) = self.breakdown_voltage {
+            current -= BREAKDOWN_CURRENT * ((-(bv + vd) / vt).exp() - (-bv / vt).exp());
+        }
+        current
+    }
+
+    fn junction_conductance(&self, vd: f64) -> f64 {
+        let vt = self.thermal_voltage();
+        let mut conductance = self.saturation_current / vt * (vd / vt).exp() + GMIN;
+        if let Some(bv) = self.breakdown_voltage {
+            conductance += BREAKDOWN_CURRENT / vt * (-(bv + vd) / vt).exp();
+        }
+        conductance
+    }
+
+    // Norton equivalent of the junction in series with the series resistance, linearized at a
+    // junction voltage, so that the current from anode to cathode is geq * V + ieq
+    fn companion(&self, vd: f64) -> (f64, f64) {
+        let (id, gd) = (self.junction_current(vd), self.junction_conductance(vd));
+        let scale = 1.0 + gd * self.series_resistance;
+        (gd / scale, (id - gd * vd) / scale)
+    }
+
+    // the junction voltage when there are `voltage` volts across the whole diode
+    fn junction_voltage(&self, voltage: f64) -> f64 {
+        if self.series_resistance == 0.0 {
+            return voltage;
+        }
+        // voltage = vd + rs * id(vd) increases with vd, so the root lies between 0 and voltage
+        let (mut low, mut high) = if voltage > 0.0 { (0.0, voltage) } else { (voltage, 0.0) };
+        for _ in 0..100 {
+            let mid = 0.5 * (low + high);
+            if mid + self.series_resistance * self.junction_current(mid) > voltage {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        0.5 * (low + high)
+    }
+
+    // limit the change in junction voltage between Newton iterations in both the forward and
+    // breakdown regions
+    fn limit(&self, next: f64, previous: f64) -> f64 {
+        let vt = self.thermal_voltage();
+        match self.breakdown_voltage {
+            Some(bv) if next < -bv => {
+                let critical = critical_voltage(vt, BREAKDOWN_CURRENT);
+                -(limit_junction(-(next + bv), -(previous + bv), vt, critical) + bv)
+            }
+            _ => limit_junction(next, previous, vt, critical_voltage(vt, self.saturation_current)),
+        }
+    }
+}
+
+// the junction voltage a diode is linearized about, 0 V before the first iteration
+fn junction_point(point: &[f64]) -> f64 {
+    point.first().copied().unwrap_or(0.0)
+}
+
+// the junction voltage above which Newton steps are limited, for a junction carrying
+// `current` * exp(v / vt)
+fn critical_voltage(vt: f64, current: f64) -> f64 {
+    vt * (vt / (std::f64::consts::SQRT_2 * current)).ln()
+}
+
+// limit the change in a junction voltage between Newton iterations (SPICE pnjlim) so that the
+// exponential can't overshoot
+fn limit_junction(next: f64, previous: f64, vt: f64, critical: f64) -> f64 {
+    if next > critical && (next - previous).abs() > 2.0 * vt {
+        if previous > 0.0 {
+            let arg = 1.0 + (next - previous) / vt;
+            if arg > 0.0 { previous + vt * arg.ln() } else { critical }
+        } else {
+            vt * (next / vt).ln()
+        }
+    } else {
+        next
+    }
+}
+
+// NPN or PNP bipolar junction transistor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BjtType {
+    Npn,
+    Pnp,
+}
+
+// Bipolar junction transistor using the Ebers-Moll transport model with the basic Gummel-Poon
+// forward Early effect. The component voltage is Vce and its current is the current into the
+// collector.
+pub struct Bjt {
+    pub component: BaseComponent,
+    pub bjt_type: BjtType,
+    pub saturation_current: f64, // Is, amps
+    pub forward_beta: f64, // Bf
+    pub reverse_beta: f64, // Br
+    pub early_voltage: Option<f64>, // Vaf, volts, None for no Early effect
+}
+impl Component for Bjt {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn terminals(&self) -> &'static [&'static str] { &["collector", "base", "emitter"] }
+    fn voltage_terminals(&self) -> (usize, usize) { (0, 2) }
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.validate()?;
+        let (vbe, vbc) = self.junction_point(stamper.operating_point());
+        self.linearized(vbe, vbc).stamp(stamper, true);
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        // small signal model at the DC operating point
+        self.validate()?;
+        let (vbe, vbc) = self.junction_point(stamper.operating_point());
+        self.linearized(vbe, vbc).stamp(stamper, false);
+        Ok(())
+    }
+
+    fn current(&self, values: &Values<f64>, _analysis: &Analysis) -> Option<f64> {
+        let (vbe, vbc) = self.junction_voltages(values);
+        Some(self.sign() * self.currents(vbe, vbc).0)
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, _omega: f64) -> Option<Complex> {
+        let (vbe, vbc) = self.junction_point(values.operating_point());
+        let small_signal = self.linearized(vbe, vbc);
+        let vbe = values.voltage(1) - values.voltage(2);
+        let vbc = values.voltage(1) - values.voltage(0);
+        Some(Complex::from(small_signal.collector.0) * vbe + Complex::from(small_signal.collector.1) * vbc)
+    }
+
+    fn is_nonlinear(&self) -> bool {
+        true
+    }
+
+    fn linearize(&self, values: &Values<f64>) -> Vec<f64> {
+        let (vbe, vbc) = self.junction_voltages(values);
+        let (previous_vbe, previous_vbc) = self.junction_point(values.operating_point());
+        let critical = critical_voltage(THERMAL_VOLTAGE, self.saturation_current);
+        vec![
+            limit_junction(vbe, previous_vbe, THERMAL_VOLTAGE, critical),
+            limit_junction(vbc, previous_vbc, THERMAL_VOLTAGE, critical),
+        ]
+    }
+}
+impl Bjt {
+    pub fn new(name: &str, bjt_type: BjtType) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            bjt_type,
+            saturation_current: 1e-16,
+            forward_beta: 100.0,
+            reverse_beta: 1.0,
+            early_voltage: None,
+        }
+    }
+
+    pub fn collector(&self) -> Option<usize> {
+        self.component.node(0)
+    }
+
+    pub fn base(&self) -> Option<usize> {
+        self.component.node(1)
+    }
+
+    pub fn emitter(&self) -> Option<usize> {
+        self.component.node(2)
+    }
+
+    fn validate(&self) -> Result<(), CircuitError> {
+        let reason = if self.saturation_current <= 0.0 {
+            "saturation current must be positive"
+        } else if self.forward_beta <= 0.0 || self.reverse_beta <= 0.0 {
+            "current gains must be positive"
+        } else if self.early_voltage.is_some_and(|vaf| vaf <= 0.0) {
+            "Early voltage must be positive"
+        } else {
+            return Ok(());
+        };
+        Err(CircuitError::InvalidValue {
+            component: self.component.name.clone(),
+            reason: reason.to_string(),
+        })
+    }
+
+    // 1 for NPN and -1 for PNP, which turns PNP voltages and currents into NPN ones
+    fn sign(&self) -> f64 {
+        match self.bjt_type {
+            BjtType::Npn => 1.0,
+            BjtType::Pnp => -1.0,
+        }
+    }
+
+    // Vbe and Vbc as seen by an NPN transistor
+    fn junction_voltages(&self, values: &Values<f64>) -> (f64, f64) {
+        let base = values.voltage(1);
+        (self.sign() * (base - values.voltage(2)), self.sign() * (base - values.voltage(0)))
+    }
+
+    // the (Vbe, Vbc) the transistor is linearized about, starting from a forward biased base-emitter junction
+    fn junction_point(&self, point: &[f64]) -> (f64, f64) {
+        match point {
+            [vbe, vbc] => (*vbe, *vbc),
+            _ => (critical_voltage(THERMAL_VOLTAGE, self.saturation_current), 0.0),
+        }
+    }
+
+    // collector and base currents of an NPN transistor and their derivatives with respect
+    // to (Vbe, Vbc)
+    #[allow(clippy::type_complexity)]
+    fn currents_and_conductances(&self, vbe: f64, vbc: f64) -> ((f64, f64), (f64, f64), (f64, f64)) {
+        let vt = THERMAL_VOLTAGE;
+        let forward = self.saturation_current * ((vbe / vt).exp() - 1.0) + GMIN * vbe;
+        let reverse = self.saturation_current * ((vbc / vt).exp() - 1.0) + GMIN * vbc;
+        let g_forward = self.saturation_current / vt * (vbe / vt).exp() + GMIN;
+        let g_reverse = self.saturation_current / vt * (vbc / vt).exp() + GMIN;
+
+        // the Early effect scales the transport current by 1 - Vbc / Vaf
+        let (early, g_early) = match self.early_voltage {
+            Some(vaf) => (1.0 - vbc / vaf, -1.0 / vaf),
+            None => (1.0, 0.0),
+        };
+        let transport = (forward - reverse) * early;
+
+        let collector = transport - reverse / self.reverse_beta;
+        let base = forward / self.forward_beta + reverse / self.reverse_beta;
+        let g_collector = (
+            g_forward * early,
+            -g_reverse * early + (forward - reverse) * g_early - g_reverse / self.reverse_beta,
+        );
+        let g_base = (g_forward / self.forward_beta, g_reverse / self.reverse_beta);
+
+        ((collector, base), g_collector, g_base)
+    }
+
+    fn currents(&self, vbe: f64, vbc: f64) -> (f64, f64) {
+        self.currents_and_conductances(vbe, vbc).0
+    }
+
+    fn linearized(&self, vbe: f64, vbc: f64) -> LinearizedBjt {
+        let ((collector, base), g_collector, g_base) = self.currents_and_conductances(vbe, vbc);
+        // the PNP sign cancels in the conductances but not in the equivalent currents
+        let sign = self.sign();
+        LinearizedBjt {
+            collector: g_collector,
+            base: g_base,
+            collector_current: sign * (collector - g_collector.0 * vbe - g_collector.1 * vbc),
+            base_current: sign * (base - g_base.0 * vbe - g_base.1 * vbc),
+        }
+    }
+}
+
+// Collector and base currents linearized as g.0 * Vbe + g.1 * Vbc + current
+struct LinearizedBjt {
+    collector: (f64, f64),
+    base: (f64, f64),
+    collector_current: f64,
+    base_current: f64,
+}
+impl LinearizedBjt {
+    // the small signal model leaves out the equivalent currents
+    fn stamp<T: Scalar>(&self, stamper: &mut Stamper<T>, large_signal: bool) {
+        let (c, b, e) = (stamper.node(0), stamper.node(1), stamper.node(2));
+        // current drawn from `row` by a linearized terminal current, and the opposite for the emitter
+        let mut stamp_terminal = |row: Option<usize>, (g_be, g_bc): (f64, f64), current: f64| {
+            for (row, sign) in [(row, 1.0), (e, -1.0)] {
+                stamper.add(row, b, T::from(sign * (g_be + g_bc)));
+                stamper.add(row, e, T::from(-sign * g_be));
+                stamper.add(row, c, T::from(-sign * g_bc));
+                if large_signal {
+                    stamper.add_rhs(row, T::from(-sign * current));
+                }
+            }
+        };
+        stamp_terminal(c, self.collector, self.collector_current);
+        stamp_terminal(b, self.base, self.base_current);
+    }
+}
+
+// N or P channel MOSFET
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MosfetType {
+    Nmos,
+    Pmos,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MosfetRegion {
+    Cutoff,
+    Triode,
+    Saturation,
+}
+
+// largest change in any MOSFET terminal voltage between Newton iterations
+const MOSFET_STEP: f64 = 1.0;
+
+// MOSFET using the level 1 Shichman-Hodges model. Drain and source are interchangeable, the
+// one at the lower potential (higher for PMOS) acts as the source. The terminals are ordered
+// so that the component voltage is Vds and its current is the current into the drain.
+// Threshold voltages follow the SPICE convention and are negative for enhancement mode PMOS.
+pub struct Mosfet {
+    pub component: BaseComponent,
+    pub mosfet_type: MosfetType,
+    pub threshold_voltage: f64, // Vto, volts
+    pub transconductance: f64, // Kp, amps per volt squared
+    pub channel_length_modulation: f64, // lambda, per volt
+    pub width: f64, // W, meters
+    pub length: f64, // L, meters
+    pub body_effect: f64, // gamma, square root volts
+    pub surface_potential: f64, // phi, volts
+}
+impl Component for Mosfet {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn terminals(&self) -> &'static [&'static str] { &["drain", "source", "gate", "body"] }
+    fn couplings(&self) -> Vec<(usize, usize, Coupling)> { vec![(0, 1, Coupling::Conductive)] }
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        self.validate()?;
+        let point = self.voltage_point(stamper.operating_point());
+        self.stamp_linearized(stamper, point, true);
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        // small signal model at the DC operating point
+        self.validate()?;
+        let point = self.voltage_point(stamper.operating_point());
+        self.stamp_linearized(stamper, point, false);
+        Ok(())
+    }
+
+    fn current(&self, values: &Values<f64>, _analysis: &Analysis) -> Option<f64> {
+        let [vgs, vds, vbs] = self.normalized(|terminal| values.voltage(terminal));
+        Some(self.sign() * self.evaluate(vgs, vds, vbs).current)
+    }
+
+    fn ac_current(&self, values: &Values<Complex>, _omega: f64) -> Option<Complex> {
+        let [vgs, vds, vbs] = self.voltage_point(values.operating_point());
+        let operation = self.evaluate(vgs, vds, vbs);
+        let source = values.voltage(1);
+        let current = Complex::from(operation.gm) * (values.voltage(2) - source)
+            + Complex::from(operation.gds) * (values.voltage(0) - source)
+            + Complex::from(operation.gmb) * (values.voltage(3) - source);
+        Some(current)
+    }
+
+    fn is_nonlinear(&self) -> bool {
+        true
+    }
+
+    fn linearize(&self, values: &Values<f64>) -> Vec<f64> {
+        let next = self.normalized(|terminal| values.voltage(terminal));
+        let previous = self.voltage_point(values.operating_point());
+        next.iter()
+            .zip(previous)
+            .map(|(next, previous)| next.clamp(previous - MOSFET_STEP, previous + MOSFET_STEP))
+            .collect()
+    }
+}
+impl Mosfet {
+    pub fn new(name: &str, mosfet_type: MosfetType) -> Self {
+        Self {
+            component: BaseComponent {
+                nodes: Vec::new(),
+                name: name.to_string(),
+                current: None,
+                voltage: None,
+            },
+            mosfet_type,
+            threshold_voltage: match mosfet_type {
+                MosfetType::Nmos => 0.7,
+                MosfetType::Pmos => -0.7,
+            },
+            transconductance: 2e-5,
+            channel_length_modulation: 0.0,
+            width: 1e-6,
+            length: 1e-6,
+            body_effect: 0.0,
+            surface_potential: 0.6,
+        }
+    }
+
+    pub fn drain(&self) -> Option<usize> {
+        self.component.node(0)
+    }
+
+    pub fn source(&self) -> Option<usize> {
+        self.component.node(1)
+    }
+
+    pub fn gate(&self) -> Option<usize> {
+        self.component.node(2)
+    }
+
+    pub fn body(&self) -> Option<usize> {
+        self.component.node(3)
+    }
+
+    // the region the transistor operates in with the given terminal voltages, indexed by terminal
+    pub fn region(&self, voltages: &[f64]) -> MosfetRegion {
+        let [vgs, vds, vbs] = self.normalized(|terminal| voltages.get(terminal).copied().unwrap_or(0.0));
+        self.evaluate(vgs, vds, vbs).region
+    }
+
+    fn validate(&self) -> Result<(), CircuitError> {
+        let reason = if self.transconductance <= 0.0 {
+            "transconductance must be positive"
+        } else if self.width <= 0.0 || self.length <= 0.0 {
+            "channel width and length must be positive"
+        } else if self.channel_length_modulation < 0.0 || self.body_effect < 0.0 {
+            "lambda and gamma can't be negative"
+        } else if self.surface_potential <= 0.0 {
+            "surface potential must be positive"
+        } else {
+            return Ok(());
+        };
+        Err(CircuitError::InvalidValue {
+            component: self.component.name.clone(),
+            reason: reason.to_string(),
+        })
+    }
+
+    // 1 for NMOS and -1 for PMOS, which turns PMOS voltages and currents into NMOS ones
+    fn sign(&self) -> f64 {
+        match self.mosfet_type {
+            MosfetType::Nmos => 1.0,
+            MosfetType::Pmos => -1.0,
+        }
+    }
+
+    // [Vgs, Vds, Vbs] as seen by an NMOS transistor
+    fn normalized(&self, voltage: impl Fn(usize) -> f64) -> [f64; 3] {
+        let source = voltage(1);
+        [voltage(2) - source, voltage(0) - source, voltage(3) - source].map(|v| self.sign() * v)
+    }
+
+    // the [Vgs, Vds, Vbs] the transistor is linearized about, starting with the channel on
+    fn voltage_point(&self, point: &[f64]) -> [f64; 3] {
+        match point {
+            [vgs, vds, vbs] => [*vgs, *vds, *vbs],
+            _ => [self.sign() * self.threshold_voltage + 1.0, 0.0, 0.0],
+        }
+    }
+
+    // drain current and its derivatives with respect to Vgs, Vds and Vbs for an NMOS transistor
+    fn evaluate(&self, vgs: f64, vds: f64, vbs: f64) -> MosfetOperation {
+        if vds < 0.0 {
+            // the drain acts as the source, so evaluate with the terminals swapped
+            let reversed = self.evaluate(vgs - vds, -vds, vbs - vds);
+            return MosfetOperation {
+                current: -reversed.current,
+                gm: -reversed.gm,
+                gds: reversed.gm + reversed.gds + reversed.gmb,
+                gmb: -reversed.gmb,
+                region: reversed.region,
+            };
+        }
+
+        let beta = self.transconductance * self.width / self.length;
+        let lambda = self.channel_length_modulation;
+        let depletion = (self.surface_potential - vbs).max(0.0).sqrt();
+        let threshold = self.sign() * self.threshold_voltage + self.body_effect * (depletion - self.surface_potential.sqrt());
+        // d(threshold) / d(Vbs) is -gamma / (2 * sqrt(phi - Vbs))
+        let body_factor = if depletion > 0.0 { self.body_effect / (2.0 * depletion) } else { 0.0 };
+        let overdrive = vgs - threshold;
+        let modulation = 1.0 + lambda * vds;
+
+        let (current, gm, gds, region) = if overdrive <= 0.0 {
+            (0.0, 0.0, 0.0, MosfetRegion::Cutoff)
+        } else if vds < overdrive {
+            let channel = beta * (overdrive * vds - 0.5 * vds * vds);
+            let gds = beta * (overdrive - vds) * modulation + channel * lambda;
+            (channel * modulation, beta * vds * modulation, gds, MosfetRegion::Triode)
+        } else {
+            let channel = 0.5 * beta * overdrive * overdrive;
+            (channel * modulation, beta * overdrive * modulation, channel * lambda, MosfetRegion::Saturation)
+        };
+
+        MosfetOperation {
+            current,
+            gm,
+            gds,
+            gmb: gm * body_factor,
+            region,
+        }
+    }
+
+    // the small signal model leaves out the equivalent current
+    fn stamp_linearized<T: Scalar>(&self, stamper: &mut Stamper<T>, [vgs, vds, vbs]: [f64; 3], large_signal: bool) {
+        let operation = self.evaluate(vgs, vds, vbs);
+        let (gm, gds, gmb) = (operation.gm, operation.gds, operation.gmb);
+        // the PMOS sign cancels in the conductances but not in the equivalent current
+        let current = self.sign() * (operation.current - gm * vgs - gds * vds - gmb * vbs);
+
+        let (d, s, g, b) = (stamper.node(0), stamper.node(1), stamper.node(2), stamper.node(3));
+        for (row, sign) in [(d, 1.0), (s, -1.0)] {
+            stamper.add(row, g, T::from(sign * gm));
+            stamper.add(row, d, T::from(sign * gds));
+            stamper.add(row, b, T::from(sign * gmb));
+            stamper.add(row, s, T::from(-sign * (gm + gds + gmb)));
+            if large_signal {
+                stamper.add_rhs(row, T::from(-sign * current));
+            }
+        }
+        stamper.conductance(0, 1, T::from(GMIN));
+    }
+}
+
+struct MosfetOperation {
+    current: f64,
+    gm: f64,
+    gds: f64,
+    gmb: f64,
+    region: MosfetRegion,
+}
+
+impl Circuit {
+    // the operating region of a MOSFET at the last DC operating point, None if the component
+    // isn't a MOSFET or the circuit hasn't been solved
+    pub fn mosfet_region(&self, name: &str) -> Option<MosfetRegion> {
+        let mosfet = self.get_component(name)?.as_any().downcast_ref::<Mosfet>()?;
+        let voltages: Option<Vec<f64>> = mosfet
+            .component
+            .nodes
+            .iter()
+            .map(|&node| self.nodes[node].voltage)
+            .collect();
+        Some(mosfet.region(&voltages?))
+    }
+}
+
+pub enum Polarity {
+    Normal,
+    Inverted,
+}
+impl Polarity {
+    // 1 for normal and -1 for inverted
+    pub fn sign(&self) -> f64 {
+        match self {
+            Polarity::Normal => 1.0,
+            Polarity::Inverted => -1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_duplicate_names_and_bad_connections() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        let error = circuit.add_component(Capacitor::new("R1", 1e-6)).unwrap_err();
+        assert_eq!(error, CircuitError::DuplicateComponent("R1".to_string()));
+        assert_eq!(error.to_string(), "A component named R1 already exists");
+        // the first component is kept and no nodes were created for the second
+        assert!(circuit.get_component("R1").unwrap().as_any().is::<Resistor>());
+        assert_eq!(circuit.nodes.len(), 2);
+
+        assert!(matches!(circuit.connect(0, 2), Err(CircuitError::UnknownNode(2))));
+        assert!(matches!(circuit.connect(1, 1), Err(CircuitError::SelfConnection(1))));
+        assert!(matches!(circuit.disconnect(7), Err(CircuitError::UnknownWire(7))));
+        assert!(matches!(circuit.remove_component("R2"), Err(CircuitError::UnknownComponent(name)) if name == "R2"));
+
+        // callers can treat it as any other error
+        let error: Box<dyn std::error::Error> = Box::new(CircuitError::SelfConnection(1));
+        assert_eq!(error.to_string(), "Cannot connect node 1 to itself");
+    }
+
+    #[test]
+    fn removed_components_leave_no_floating_nodes() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 5.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", 100.0)).unwrap();
+        circuit.add_component(Resistor::new("R2", 100.0)).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("R1", 1, "in"), ("R1", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        let orphans = circuit.get_component("R2").unwrap().component().nodes.clone();
+        circuit.remove_component("R2").unwrap();
+
+        assert!(circuit.validate().is_empty());
+        circuit.solve_dc().unwrap();
+        assert_eq!(circuit.net_voltage("in"), Some(5.0));
+        assert!(orphans.iter().all(|&node| circuit.nodes[node].voltage.is_none()));
+        circuit.solve_ac(1e3).unwrap();
+        circuit.simulate_transient(1e-3, 1e-4).unwrap();
+    }
+
+    // a source driving a diode from `anode` to ground through a resistor
+    fn diode_circuit(source: f64, resistance: f64, diode: Diode) -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", source, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("R1", resistance)).unwrap();
+        circuit.add_component(diode).unwrap();
+        circuit.attach("V1", 1, "in").unwrap();
+        circuit.attach("V1", 2, "0").unwrap();
+        circuit.attach("R1", 1, "in").unwrap();
+        circuit.attach("R1", 2, "anode").unwrap();
+        circuit.attach("D1", 1, "anode").unwrap();
+        circuit.attach("D1", 2, "0").unwrap();
+        circuit
+    }
+
+    fn solved_diode(circuit: &mut Circuit) -> (f64, f64) {
+        circuit.solve_dc().unwrap();
+        let base = circuit.get_component("D1").unwrap().component();
+        (base.voltage.unwrap(), base.current.unwrap())
+    }
+
+    #[test]
+    fn limits_large_forward_steps_logarithmically() {
+        let vt = THERMAL_VOLTAGE;
+        let critical = critical_voltage(vt, 1e-14);
+        assert!(critical > 0.6 && critical < 0.8);
+
+        // below the critical voltage or within two thermal voltages the step is kept
+        assert_eq!(limit_junction(0.5, 0.0, vt, critical), 0.5);
+        assert_eq!(limit_junction(critical + 0.01, critical, vt, critical), critical + 0.01);
+
+        // from a forward biased junction the step becomes logarithmic
+        let limited = limit_junction(5.0, 0.7, vt, critical);
+        assert!((limited - (0.7 + vt * (1.0 + 4.3 / vt).ln())).abs() < 1e-12);
+        assert!(limited < 1.0);
+
+        // from a reverse biased junction the voltage is compressed to vt * ln(v / vt)
+        assert!((limit_junction(5.0, -1.0, vt, critical) - vt * (5.0 / vt).ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn finds_the_forward_operating_point() {
+        let (voltage, current) = solved_diode(&mut diode_circuit(5.0, 1e3, Diode::new("D1")));
+        assert!((voltage - 0.6925).abs() < 1e-3, "{}", voltage);
+        // the resistor and the diode carry the same current
+        assert!(((5.0 - voltage) / 1e3 - current).abs() < 1e-9);
+        assert!((current - 1e-14 * ((voltage / THERMAL_VOLTAGE).exp() - 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn converges_from_far_above_the_knee() {
+        // without limiting the first Newton step would evaluate exp(100 / vt)
+        let (voltage, current) = solved_diode(&mut diode_circuit(100.0, 1.0, Diode::new("D1")));
+        assert!(voltage > 0.8 && voltage < 1.0);
+        assert!(((100.0 - voltage) - current).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clamps_at_the_breakdown_voltage() {
+        let mut zener = Diode::new("D1");
+        zener.breakdown_voltage = Some(5.1);
+        let (voltage, current) = solved_diode(&mut diode_circuit(-12.0, 1e3, zener));
+        assert!(voltage < -5.1 && voltage > -5.4, "{}", voltage);
+        assert!(((-12.0 - voltage) / 1e3 - current).abs() < 1e-9);
+    }
+
+    #[test]
+    fn allocates_a_node_for_every_terminal() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        circuit.add_component(Bjt::new("Q1", BjtType::Npn)).unwrap();
+        circuit.add_component(Mosfet::new("M1", MosfetType::Nmos)).unwrap();
+        assert_eq!(circuit.nodes.len(), 2 + 3 + 4);
+
+        let q1 = circuit.get_component("Q1").unwrap();
+        assert_eq!(q1.terminals(), ["collector", "base", "emitter"]);
+        assert_eq!(q1.component().nodes, [2, 3, 4]);
+        assert_eq!(q1.terminal_node("emitter"), Some(4));
+        assert_eq!(q1.terminal_node("drain"), None);
+        assert_eq!(circuit.get_component("M1").unwrap().terminal_node("body"), Some(8));
+        // every new node is connected to its component only
+        for node in &circuit.nodes[2..5] {
+            assert!(matches!(node.connected.as_slice(), [ConnectionItem::Component(name)] if name == "Q1"));
+        }
+
+        circuit.attach_terminal("M1", "gate", "g").unwrap();
+        assert_eq!(circuit.net_node("g"), Some(7));
+        let error = circuit.attach_terminal("M1", "collector", "g").unwrap_err();
+        assert_eq!(
+            error,
+            CircuitError::UnknownTerminal {
+                component: "M1".to_string(),
+                terminal: "collector".to_string(),
+            }
+        );
+        assert!(circuit.attach("R1", 3, "g").is_err());
+    }
+
+    // V1 (1 V DC and AC) across R1 (1 ohm) controls `source`, which drives 2 ohms from "out"
+    fn controlled_source(source: impl Component + 'static) -> Circuit {
+        let mut input = VoltageSource::new("V1", 1.0, Polarity::Normal);
+        input.ac_magnitude = 1.0;
+        let name = source.component().name.clone();
+        let voltage_controlled = source.terminals().len() == 4;
+        let mut circuit = Circuit::new();
+        circuit.add_component(input).unwrap();
+        circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+        circuit.add_component(Resistor::new("RL", 2.0)).unwrap();
+        circuit.add_component(source).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("R1", 1, "in"), ("R1", 2, "0"), ("RL", 1, "out"), ("RL", 2, "0")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        circuit.attach(&name, 1, "out").unwrap();
+        circuit.attach(&name, 2, "0").unwrap();
+        if voltage_controlled {
+            circuit.attach(&name, 3, "in").unwrap();
+            circuit.attach(&name, 4, "0").unwrap();
+        }
+        circuit
+    }
+
+    #[test]
+    fn dependent_sources_follow_their_control_in_dc_and_ac() {
+        // the output voltage and the current through the source from node1 to node2
+        let cases = [
+            ("E1", controlled_source(Vcvs::new("E1", 3.0)), 3.0, -1.5),
+            ("G1", controlled_source(Vccs::new("G1", 0.5)), 1.0, -0.5),
+            ("H1", controlled_source(Ccvs::new("H1", 4.0, "R1")), 4.0, -2.0),
+            ("F1", controlled_source(Cccs::new("F1", 2.0, "R1")), 4.0, -2.0),
+        ];
+        for (name, mut circuit, voltage, current) in cases {
+            circuit.solve_dc().unwrap();
+            assert!((circuit.net_voltage("out").unwrap() - voltage).abs() < 1e-9, "{}", name);
+            let base = circuit.get_component(name).unwrap().component();
+            assert!((base.current.unwrap() - current).abs() < 1e-9, "{}", name);
+
+            let solution = circuit.solve_ac(1e3).unwrap();
+            let out = solution.node_voltage(circuit.net_node("out").unwrap()).unwrap();
+            assert!((out - Complex::from(voltage)).magnitude() < 1e-9, "{}", name);
+            assert!((solution.current(name).unwrap() - Complex::from(current)).magnitude() < 1e-9, "{}", name);
+        }
+    }
+
+    #[test]
+    fn current_control_must_name_a_component() {
+        let mut circuit = controlled_source(Cccs::new("F1", 2.0, "R9"));
+        assert_eq!(circuit.solve_dc().unwrap_err(), CircuitError::UnknownComponent("R9".to_string()));
+    }
+
+    #[test]
+    fn bjt_reports_vce_and_the_collector_current() {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 10.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("RB", 1e6)).unwrap();
+        circuit.add_component(Resistor::new("RC", 1e3)).unwrap();
+        circuit.add_component(Bjt::new("Q1", BjtType::Npn)).unwrap();
+        for (component, terminal, net) in [("V1", 1, "vcc"), ("V1", 2, "0"), ("RB", 1, "vcc"), ("RB", 2, "b"), ("RC", 1, "vcc"), ("RC", 2, "c")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        for (terminal, net) in [("collector", "c"), ("base", "b"), ("emitter", "0")] {
+            circuit.attach_terminal("Q1", terminal, net).unwrap();
+        }
+        circuit.solve_dc().unwrap();
+
+        let (vb, vc) = (circuit.net_voltage("b").unwrap(), circuit.net_voltage("c").unwrap());
+        assert!(vb > 0.6 && vb < 0.8, "{}", vb);
+        let q1 = circuit.get_component("Q1").unwrap().component();
+        assert!((q1.voltage.unwrap() - vc).abs() < 1e-9);
+        assert!((q1.current.unwrap() - (10.0 - vc) / 1e3).abs() < 1e-9);
+        // forward active with a current gain of about Bf
+        let base_current = (10.0 - vb) / 1e6;
+        assert!((q1.current.unwrap() / base_current - 100.0).abs() < 1.0);
+    }
+
+    // an inverting amplifier with a gain of -10 driven by 0.5 V
+    fn inverting_amplifier(op_amp: OpAmp) -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("V1", 0.5, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("RIN", 1e3)).unwrap();
+        circuit.add_component(Resistor::new("RF", 1e4)).unwrap();
+        circuit.add_component(op_amp).unwrap();
+        for (component, terminal, net) in [("V1", 1, "in"), ("V1", 2, "0"), ("RIN", 1, "in"), ("RIN", 2, "sum"), ("RF", 1, "sum"), ("RF", 2, "out")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        for (terminal, net) in [("output", "out"), ("reference", "0"), ("non_inverting", "0"), ("inverting", "sum")] {
+            circuit.attach_terminal("U1", terminal, net).unwrap();
+        }
+        circuit
+    }
+
+    #[test]
+    fn ideal_op_amp_holds_its_inputs_together() {
+        let mut circuit = inverting_amplifier(OpAmp::ideal("U1"));
+        circuit.solve_dc().unwrap();
+        assert!((circuit.net_voltage("out").unwrap() + 5.0).abs() < 1e-9);
+        assert!(circuit.net_voltage("sum").unwrap().abs() < 1e-12);
+        // the feedback current flows into the output and through the op amp to the reference
+        assert!((circuit.get_component("U1").unwrap().component().current.unwrap() - 0.5e-3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn finite_gain_op_amp_falls_short_of_the_ideal_gain() {
+        let mut circuit = inverting_amplifier(OpAmp::new("U1", 1e3));
+        circuit.solve_dc().unwrap();
+        // closed loop gain -(Rf / Rin) / (1 + (1 + Rf / Rin) / A)
+        let expected = -5.0 / (1.0 + 11.0 / 1e3);
+        assert!((circuit.net_voltage("out").unwrap() - expected).abs() < 1e-9);
+
+        // the gain rolls off past the gain bandwidth product divided by the noise gain
+        let mut op_amp = OpAmp::new("U1", 1e5);
+        op_amp.gain_bandwidth = Some(1.1e6);
+        let mut circuit = inverting_amplifier(op_amp);
+        circuit.get_component_mut("V1").unwrap().as_any_mut().downcast_mut::<VoltageSource>().unwrap().ac_magnitude = 1.0;
+        let out = circuit.net_node("out").unwrap();
+        let low = circuit.solve_ac(10.0).unwrap().node_voltage(out).unwrap().magnitude();
+        let corner = circuit.solve_ac(1e5).unwrap().node_voltage(out).unwrap().magnitude();
+        assert!((low - 10.0).abs() < 0.01, "{}", low);
+        assert!((corner / low - 0.5_f64.sqrt()).abs() < 0.01, "{}", corner / low);
+    }
+
+    #[test]
+    fn op_amp_output_saturates_at_the_rails() {
+        let mut op_amp = OpAmp::new("U1", 1e5);
+        op_amp.rails = Some((-3.0, 3.0));
+        let mut circuit = inverting_amplifier(op_amp);
+        circuit.solve_dc().unwrap();
+        let out = circuit.net_voltage("out").unwrap();
+        assert!((-3.0..-3.0 + RAIL_KNEE * 6.0).contains(&out), "{}", out);
+
+        // within the rails the output is linear
+        circuit.get_component_mut("V1").unwrap().as_any_mut().downcast_mut::<VoltageSource>().unwrap().voltage = -0.1;
+        circuit.solve_dc().unwrap();
+        assert!((circuit.net_voltage("out").unwrap() - 1.0).abs() < 1e-3);
+
+        let mut op_amp = OpAmp::new("U1", 1e5);
+        op_amp.rails = Some((3.0, -3.0));
+        let error = inverting_amplifier(op_amp).solve_dc().unwrap_err();
+        assert!(matches!(error, CircuitError::InvalidValue { component, .. } if component == "U1"));
+    }
+
+    #[test]
+    fn mosfet_regions_follow_the_terminal_voltages() {
+        // voltages are indexed by terminal: drain, source, gate, body
+        let nmos = Mosfet::new("M1", MosfetType::Nmos);
+        assert_eq!(nmos.region(&[5.0, 0.0, 0.5, 0.0]), MosfetRegion::Cutoff);
+        assert_eq!(nmos.region(&[0.5, 0.0, 3.0, 0.0]), MosfetRegion::Triode);
+        assert_eq!(nmos.region(&[5.0, 0.0, 2.0, 0.0]), MosfetRegion::Saturation);
+        // with the drain below the source the two swap roles
+        assert_eq!(nmos.region(&[0.0, 5.0, 3.0, 0.0]), MosfetRegion::Saturation);
+
+        let pmos = Mosfet::new("M2", MosfetType::Pmos);
+        assert_eq!(pmos.region(&[0.0, 5.0, 5.0, 5.0]), MosfetRegion::Cutoff);
+        assert_eq!(pmos.region(&[4.5, 5.0, 0.0, 5.0]), MosfetRegion::Triode);
+        assert_eq!(pmos.region(&[0.0, 5.0, 2.0, 5.0]), MosfetRegion::Saturation);
+
+        // reverse body bias raises the threshold
+        let mut body = Mosfet::new("M3", MosfetType::Nmos);
+        body.body_effect = 0.5;
+        assert_eq!(body.region(&[5.0, 0.0, 0.9, 0.0]), MosfetRegion::Saturation);
+        assert_eq!(body.region(&[5.0, 0.0, 0.9, -3.0]), MosfetRegion::Cutoff);
+    }
+
+    #[test]
+    fn reports_the_region_at_the_dc_operating_point() {
+        // common source stage with a 10k drain resistor from 5 V
+        let mut circuit = Circuit::new();
+        circuit.add_component(VoltageSource::new("VDD", 5.0, Polarity::Normal)).unwrap();
+        circuit.add_component(VoltageSource::new("VG", 0.0, Polarity::Normal)).unwrap();
+        circuit.add_component(Resistor::new("RD", 1e4)).unwrap();
+        circuit.add_component(Mosfet::new("M1", MosfetType::Nmos)).unwrap();
+        for (component, terminal, net) in [("VDD", 1, "vdd"), ("VDD", 2, "0"), ("VG", 1, "g"), ("VG", 2, "0"), ("RD", 1, "vdd"), ("RD", 2, "d")] {
+            circuit.attach(component, terminal, net).unwrap();
+        }
+        for (terminal, net) in [("drain", "d"), ("source", "0"), ("gate", "g"), ("body", "0")] {
+            circuit.attach_terminal("M1", terminal, net).unwrap();
+        }
+        assert_eq!(circuit.mosfet_region("M1"), None);
+
+        for (gate, region) in [(0.5, MosfetRegion::Cutoff), (1.5, MosfetRegion::Saturation), (5.0, MosfetRegion::Triode)] {
+            circuit.get_component_mut("VG").unwrap().as_any_mut().downcast_mut::<VoltageSource>().unwrap().voltage = gate;
+            circuit.solve_dc().unwrap();
+            assert_eq!(circuit.mosfet_region("M1"), Some(region), "{}", gate);
+        }
+        assert_eq!(circuit.mosfet_region("RD"), None);
+        assert_eq!(circuit.mosfet_region("M2"), None);
+    }
+
+    #[test]
+    fn series_resistance_drops_part_of_the_voltage() {
+        let mut diode = Diode::new("D1");
+        diode.series_resistance = 10.0;
+        let (voltage, current) = solved_diode(&mut diode_circuit(5.0, 1e3, diode));
+        let junction = voltage - 10.0 * current;
+        assert!((current - 1e-14 * ((junction / THERMAL_VOLTAGE).exp() - 1.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,302 @@
+// src/validation.rs
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use crate::nets::UnionFind;
+use crate::types::Circuit;
+
+// How current passes between two terminals of a component
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coupling {
+    // any path with a defined current-voltage relation, such as a resistor or a junction
+    Conductive,
+    // open at DC
+    Capacitive,
+    // a short at DC
+    Inductive,
+    // the voltage across the terminals is set whatever the current
+    VoltageSource,
+    // the current between the terminals is set whatever the voltage
+    CurrentSource,
+}
+
+// A problem with a circuit's topology that leaves its MNA system singular
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    // a terminal that nothing else is connected to
+    UnconnectedTerminal { component: String, terminal: String },
+    // nodes with no path of any kind to a ground node
+    FloatingNodes { nodes: Vec<usize>, components: Vec<String> },
+    // a loop made only of voltage sources and inductors, which are shorts at DC, so that
+    // the source voltages can't all be met
+    VoltageSourceLoop(Vec<String>),
+    // current sources that are the only connection between part of the circuit and ground
+    CurrentSourceCutset(Vec<String>),
+    // capacitors (and current sources) that are the only connection between part of the
+    // circuit and ground, which leaves it floating at DC
+    CapacitorCutset(Vec<String>),
+}
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::UnconnectedTerminal { component, terminal } => write!(f, "Terminal {} of {} is not connected to anything", terminal, component),
+            Diagnostic::FloatingNodes { nodes, components } => {
+                let nodes: Vec<String> = nodes.iter().map(|node| node.to_string()).collect();
+                write!(f, "Nodes {} ({}) have no path to ground", nodes.join(", "), components.join(", "))
+            }
+            Diagnostic::VoltageSourceLoop(components) => write!(f, "{} form a loop of voltage sources and inductors", components.join(", ")),
+            Diagnostic::CurrentSourceCutset(components) => write!(f, "Current sources {} are the only path to part of the circuit", components.join(", ")),
+            Diagnostic::CapacitorCutset(components) => write!(f, "Part of the circuit is only reached through {}, which are open at DC", components.join(", ")),
+        }
+    }
+}
+
+// a coupling between two nets
+struct Edge<'a> {
+    component: &'a str,
+    nets: (usize, usize),
+    coupling: Coupling,
+}
+
+impl Circuit {
+    // Check the circuit for topologies that would make it unsolvable. An empty list means
+    // no problems were found.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let net_map = self.net_map();
+        let net_count = net_map.iter().map(|net| net + 1).max().unwrap_or(0);
+
+        let mut ground_nets = vec![false; net_count];
+        for node in self.nodes.iter().filter(|node| node.ground) {
+            ground_nets[net_map[node.id]] = true;
+        }
+
+        let mut names: Vec<&String> = self.components.keys().collect();
+        names.sort();
+
+        // every terminal on each net
+        let mut terminals: Vec<Vec<(&str, &str)>> = vec![Vec::new(); net_count];
+        let mut edges = Vec::new();
+        for name in &names {
+            let component = self.components[*name].as_ref();
+            let nodes = &component.component().nodes;
+            for (terminal, &node) in component.terminals().iter().zip(nodes) {
+                terminals[net_map[node]].push((name.as_str(), *terminal));
+            }
+            for (first, second, coupling) in component.couplings() {
+                edges.push(Edge {
+                    component: name.as_str(),
+                    nets: (net_map[nodes[first]], net_map[nodes[second]]),
+                    coupling,
+                });
+            }
+        }
+
+        let mut diagnostics = Vec::new();
+        for (net, terminals) in terminals.iter().enumerate() {
+            if let [(component, terminal)] = terminals.as_slice() {
+                if !ground_nets[net] {
+                    diagnostics.push(Diagnostic::UnconnectedTerminal {
+                        component: component.to_string(),
+                        terminal: terminal.to_string(),
+                    });
+                }
+            }
+        }
+
+        // the nets reached from ground by every coupling, by everything but current sources,
+        // and by what conducts at DC
+        let all = Groups::new(net_count, &edges, &ground_nets, |_| true);
+        let sourced = Groups::new(net_count, &edges, &ground_nets, |coupling| coupling != Coupling::CurrentSource);
+        let dc = Groups::new(net_count, &edges, &ground_nets, |coupling| {
+            matches!(coupling, Coupling::Conductive | Coupling::Inductive | Coupling::VoltageSource)
+        });
+
+        // nets with nothing connected are left over from removed components, not floating
+        let connected = self.connected_nets(&net_map);
+        for group in all.floating().into_iter().filter(|group| group.iter().any(|&net| connected[net])) {
+            let mut nodes: Vec<usize> = (0..net_map.len()).filter(|&node| group.contains(&net_map[node])).collect();
+            nodes.sort();
+            let mut components: Vec<String> = group.iter().flat_map(|&net| terminals[net].iter().map(|(name, _)| name.to_string())).collect();
+            components.sort();
+            components.dedup();
+            diagnostics.push(Diagnostic::FloatingNodes { nodes, components });
+        }
+
+        // parts of the circuit only floating once the current sources are taken out are cut
+        // off by them, and parts only floating once capacitors are taken out too are cut off
+        // by capacitors
+        for group in sourced.floating() {
+            if all.is_grounded(&group) {
+                diagnostics.push(Diagnostic::CurrentSourceCutset(crossing(&edges, &group)));
+            }
+        }
+        for group in dc.floating() {
+            if sourced.is_grounded(&group) {
+                diagnostics.push(Diagnostic::CapacitorCutset(crossing(&edges, &group)));
+            }
+        }
+
+        diagnostics.extend(voltage_source_loops(net_count, &edges).into_iter().map(Diagnostic::VoltageSourceLoop));
+        diagnostics
+    }
+}
+
+// groups of nets joined by the couplings that pass a filter
+struct Groups {
+    labels: Vec<usize>,
+    grounded: Vec<bool>,
+}
+impl Groups {
+    fn new(net_count: usize, edges: &[Edge], ground_nets: &[bool], filter: impl Fn(Coupling) -> bool) -> Self {
+        let mut sets = UnionFind::new(net_count);
+        for edge in edges.iter().filter(|edge| filter(edge.coupling)) {
+            sets.union(edge.nets.0, edge.nets.1);
+        }
+        let labels = sets.labels();
+        let mut grounded = vec![false; net_count];
+        for net in (0..net_count).filter(|&net| ground_nets[net]) {
+            grounded[labels[net]] = true;
+        }
+        Self { labels, grounded }
+    }
+
+    fn is_grounded(&self, group: &HashSet<usize>) -> bool {
+        group.iter().any(|&net| self.grounded[self.labels[net]])
+    }
+
+    // the nets of every group without a ground, in order of their lowest net
+    fn floating(&self) -> Vec<HashSet<usize>> {
+        let mut index: Vec<Option<usize>> = vec![None; self.labels.len()];
+        let mut groups: Vec<HashSet<usize>> = Vec::new();
+        for (net, &label) in self.labels.iter().enumerate() {
+            if self.grounded[label] {
+                continue;
+            }
+            let group = *index[label].get_or_insert_with(|| {
+                groups.push(HashSet::new());
+                groups.len() - 1
+            });
+            groups[group].insert(net);
+        }
+        groups
+    }
+}
+
+// the components with a coupling leaving a group of nets
+fn crossing(edges: &[Edge], group: &HashSet<usize>) -> Vec<String> {
+    let mut components: Vec<String> = edges
+        .iter()
+        .filter(|edge| group.contains(&edge.nets.0) != group.contains(&edge.nets.1))
+        .map(|edge| edge.component.to_string())
+        .collect();
+    components.dedup();
+    components
+}
+
+// Every voltage source or inductor that closes a loop of voltage sources and inductors, listed with the
+// components around the loop. The inductors go in first, so that a loop is reported from one
+// of its sources when it has any.
+fn voltage_source_loops(net_count: usize, edges: &[Edge]) -> Vec<Vec<String>> {
+    let mut sets = UnionFind::new(net_count);
+    let mut adjacent: Vec<Vec<(usize, &str)>> = vec![Vec::new(); net_count];
+    let mut loops = Vec::new();
+
+    let inductors = edges.iter().filter(|edge| edge.coupling == Coupling::Inductive);
+    let sources = edges.iter().filter(|edge| edge.coupling == Coupling::VoltageSource);
+    for edge in inductors.chain(sources) {
+        let (a, b) = edge.nets;
+        if sets.find(a) != sets.find(b) {
+            sets.union(a, b);
+            adjacent[a].push((b, edge.component));
+            adjacent[b].push((a, edge.component));
+            continue;
+        }
+
+        // the sources and inductors already joining the two nets, found by a breadth first search from a to b
+        let mut previous: Vec<Option<(usize, &str)>> = vec![None; net_count];
+        let mut queue = VecDeque::from([a]);
+        while let Some(net) = queue.pop_front() {
+            if net == b {
+                break;
+            }
+            for &(next, component) in &adjacent[net] {
+                if next != a && previous[next].is_none() {
+                    previous[next] = Some((net, component));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut components = vec![edge.component.to_string()];
+        let mut net = b;
+        while let Some((before, component)) = previous[net] {
+            components.push(component.to_string());
+            net = before;
+        }
+        loops.push(components);
+    }
+
+    loops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spice::parse_spice;
+
+    fn diagnose(netlist: &str) -> Vec<Diagnostic> {
+        parse_spice(netlist).unwrap().validate()
+    }
+
+    #[test]
+    fn accepts_a_solvable_circuit() {
+        assert!(diagnose("t\nV1 a 0 5\nR1 a b 1k\nC1 b 0 1u\nL1 b c 1m\nR2 c 0 1k\n").is_empty());
+    }
+
+    #[test]
+    fn finds_loops_of_voltage_sources_and_inductors() {
+        let diagnostics = diagnose("t\nV1 a 0 5\nV2 a 0 3\nR1 a 0 1k\n");
+        assert_eq!(diagnostics, vec![Diagnostic::VoltageSourceLoop(vec!["V2".to_string(), "V1".to_string()])]);
+
+        // L1 is a short at DC, so it fixes the voltage across V1 too
+        let diagnostics = diagnose("t\nV1 a 0 5\nL1 a 0 1m\nR1 a 0 1k\n");
+        assert_eq!(diagnostics, vec![Diagnostic::VoltageSourceLoop(vec!["V1".to_string(), "L1".to_string()])]);
+        assert_eq!(diagnostics[0].to_string(), "V1, L1 form a loop of voltage sources and inductors");
+    }
+
+    #[test]
+    fn finds_parts_only_reached_through_current_sources() {
+        let diagnostics = diagnose("t\nI1 0 a 1m\nR1 a b 1k\nR2 b a 1k\n");
+        assert_eq!(diagnostics, vec![Diagnostic::CurrentSourceCutset(vec!["I1".to_string()])]);
+    }
+
+    #[test]
+    fn finds_parts_only_reached_through_capacitors() {
+        let diagnostics = diagnose("t\nV1 a 0 1\nC1 a b 1u\nR1 b c 1k\nR2 c b 1k\n");
+        assert_eq!(diagnostics, vec![Diagnostic::CapacitorCutset(vec!["C1".to_string()])]);
+    }
+
+    #[test]
+    fn finds_unconnected_terminals() {
+        let diagnostics = diagnose("t\nV1 a 0 1\nR1 a b 1k\n");
+        let expected = Diagnostic::UnconnectedTerminal {
+            component: "R1".to_string(),
+            terminal: "node2".to_string(),
+        };
+        assert_eq!(diagnostics, vec![expected]);
+        assert_eq!(diagnostics[0].to_string(), "Terminal node2 of R1 is not connected to anything");
+    }
+
+    #[test]
+    fn finds_floating_nodes() {
+        let circuit = parse_spice("t\nV1 a 0 1\nR1 a 0 1k\nR2 b c 1k\nR3 c b 1k\n").unwrap();
+        let mut nodes: Vec<usize> = ["R2", "R3"].iter().flat_map(|name| circuit.get_component(name).unwrap().component().nodes.clone()).collect();
+        nodes.sort();
+        let expected = Diagnostic::FloatingNodes {
+            nodes,
+            components: vec!["R2".to_string(), "R3".to_string()],
+        };
+        assert_eq!(circuit.validate(), vec![expected]);
+    }
+}
@@ -0,0 +1,105 @@
+// src/waveform.rs
+
+use std::f64::consts::PI;
+
+// Time varying value of an independent source during a transient simulation.
+// The parameters follow the SPICE source functions of the same names.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Waveform {
+    // offset + amplitude * exp(-damping * (t - delay)) * sin(2 pi frequency (t - delay) + phase),
+    // holding its value at t = delay before the delay
+    Sin {
+        offset: f64,
+        amplitude: f64,
+        frequency: f64, // hertz
+        delay: f64, // seconds
+        damping: f64, // per second
+        phase: f64, // degrees
+    },
+    // steps from initial to pulsed after the delay, staying there for width seconds before
+    // returning, and repeating every period seconds when a period is given
+    Pulse {
+        initial: f64,
+        pulsed: f64,
+        delay: f64,
+        rise: f64,
+        fall: f64,
+        width: f64,
+        period: Option<f64>,
+    },
+    // (time, value) points joined by straight lines, holding the first and last values outside them
+    Pwl(Vec<(f64, f64)>),
+    // rises exponentially from initial towards pulsed after rise_delay, then falls back towards
+    // initial after fall_delay
+    Exp {
+        initial: f64,
+        pulsed: f64,
+        rise_delay: f64,
+        rise_time_constant: f64,
+        fall_delay: f64,
+        fall_time_constant: f64,
+    },
+}
+impl Waveform {
+    pub fn value(&self, time: f64) -> f64 {
+        match self {
+            Waveform::Sin { offset, amplitude, frequency, delay, damping, phase } => {
+                let t = (time - delay).max(0.0);
+                let phase = phase.to_radians();
+                offset + amplitude * (-damping * t).exp() * (2.0 * PI * frequency * t + phase).sin()
+            }
+            Waveform::Pulse { initial, pulsed, delay, rise, fall, width, period } => {
+                if time < *delay {
+                    return *initial;
+                }
+                let mut t = time - delay;
+                if let Some(period) = period.filter(|period| *period > 0.0) {
+                    t %= period;
+                }
+                if t < *rise {
+                    initial + (pulsed - initial) * t / rise
+                } else if t < rise + width {
+                    *pulsed
+                } else if t < rise + width + fall {
+                    pulsed + (initial - pulsed) * (t - rise - width) / fall
+                } else {
+                    *initial
+                }
+            }
+            Waveform::Pwl(points) => {
+                let (first, last) = match (points.first(), points.last()) {
+                    (Some(first), Some(last)) => (first, last),
+                    _ => return 0.0,
+                };
+                if time <= first.0 {
+                    return first.1;
+                }
+                if time >= last.0 {
+                    return last.1;
+                }
+                let after = points.iter().position(|(t, _)| *t > time).unwrap();
+                let ((t0, v0), (t1, v1)) = (points[after - 1], points[after]);
+                v0 + (v1 - v0) * (time - t0) / (t1 - t0)
+            }
+            Waveform::Exp { initial, pulsed, rise_delay, rise_time_constant, fall_delay, fall_time_constant } => {
+                let mut value = *initial;
+                if time > *rise_delay {
+                    value += (pulsed - initial) * (1.0 - (-(time - rise_delay) / rise_time_constant).exp());
+                }
+                if time > *fall_delay {
+                    value += (initial - pulsed) * (1.0 - (-(time - fall_delay) / fall_time_constant).exp());
+                }
+                value
+            }
+        }
+    }
+
+    // the value a transient simulation starts from, which for a pulse is the value just before
+    // t = 0 so that a pulse with no delay or rise time still steps up after the start
+    pub fn start_value(&self) -> f64 {
+        match self {
+            Waveform::Pulse { initial, .. } => *initial,
+            _ => self.value(0.0),
+        }
+    }
+}
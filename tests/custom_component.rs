@@ -0,0 +1,114 @@
+// tests/custom_component.rs
+
+// Components defined outside the crate, solved through the public stamping interface
+
+use std::any::Any;
+
+use circuit_solver::complex::Complex;
+use circuit_solver::error::CircuitError;
+use circuit_solver::stamp::{Analysis, Stamper, Values};
+use circuit_solver::types::{BaseComponent, Cccs, Circuit, Component, Resistor};
+
+fn base(name: &str) -> BaseComponent {
+    BaseComponent {
+        nodes: Vec::new(),
+        name: name.to_string(),
+        current: None,
+        voltage: None,
+    }
+}
+
+// an emf with internal resistance: V(node1) - V(node2) - r * I = emf
+struct Battery {
+    component: BaseComponent,
+    emf: f64,
+    resistance: f64,
+}
+impl Component for Battery {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn branch_count(&self, _analysis: &Analysis) -> usize {
+        1
+    }
+
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        stamper.voltage(0, 1, 0, self.emf);
+        stamper.add(stamper.branch(0), stamper.branch(0), -self.resistance);
+        Ok(())
+    }
+
+    fn stamp_ac(&self, stamper: &mut Stamper<Complex>, _omega: f64) -> Result<(), CircuitError> {
+        stamper.voltage(0, 1, 0, Complex::default());
+        stamper.add(stamper.branch(0), stamper.branch(0), Complex::from(-self.resistance));
+        Ok(())
+    }
+}
+
+// a conductance without a branch, which has to report its own current
+struct Leak {
+    component: BaseComponent,
+    conductance: f64,
+}
+impl Component for Leak {
+    fn component(&self) -> &BaseComponent { &self.component }
+    fn component_mut(&mut self) -> &mut BaseComponent { &mut self.component }
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn stamp(&self, stamper: &mut Stamper<f64>, _analysis: &Analysis) -> Result<(), CircuitError> {
+        stamper.conductance(0, 1, self.conductance);
+        Ok(())
+    }
+
+    fn current(&self, values: &Values<f64>, _analysis: &Analysis) -> Option<f64> {
+        Some(self.conductance * (values.voltage(0) - values.voltage(1)))
+    }
+}
+
+fn battery_circuit() -> Circuit {
+    let mut circuit = Circuit::new();
+    let battery = Battery {
+        component: base("B1"),
+        emf: 9.0,
+        resistance: 1.0,
+    };
+    circuit.add_component(battery).unwrap();
+    circuit.add_component(Leak { component: base("X1"), conductance: 0.125 }).unwrap();
+    for (component, terminal, net) in [("B1", 1, "plus"), ("B1", 2, "0"), ("X1", 1, "plus"), ("X1", 2, "0")] {
+        circuit.attach(component, terminal, net).unwrap();
+    }
+    circuit
+}
+
+#[test]
+fn solves_components_defined_outside_the_crate() {
+    let mut circuit = battery_circuit();
+    circuit.solve_dc().unwrap();
+
+    // 9 V across 1 ohm inside and 8 ohms outside
+    assert!((circuit.net_voltage("plus").unwrap() - 8.0).abs() < 1e-9);
+    let battery = circuit.get_component("B1").unwrap().component();
+    assert!((battery.current.unwrap() + 1.0).abs() < 1e-9);
+    let leak = circuit.get_component("X1").unwrap().component();
+    assert!((leak.current.unwrap() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn branch_currents_of_custom_components_can_control_sources() {
+    let mut circuit = battery_circuit();
+    circuit.add_component(Cccs::new("F1", 2.0, "B1")).unwrap();
+    circuit.add_component(Resistor::new("R1", 1.0)).unwrap();
+    for (component, terminal, net) in [("F1", 1, "out"), ("F1", 2, "0"), ("R1", 1, "out"), ("R1", 2, "0")] {
+        circuit.attach(component, terminal, net).unwrap();
+    }
+    circuit.solve_dc().unwrap();
+    assert!((circuit.net_voltage("out").unwrap() + 2.0).abs() < 1e-9);
+}
+
+#[test]
+fn custom_components_without_stamp_ac_refuse_ac_analysis() {
+    let circuit = battery_circuit();
+    let error = circuit.solve_ac(1e3).err().unwrap();
+    assert_eq!(error, CircuitError::InvalidAnalysis("X1 does not support AC analysis".to_string()));
+}